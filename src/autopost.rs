@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serenity::all::{ChannelId, CreateMessage, GuildId};
+use serenity::prelude::*;
+use tokio::time::{Duration, Instant};
+
+use crate::database::{ChannelFlags, Database};
+use crate::settings::GuildSettings;
+use crate::utils::helpers::{generate_markov_message, get_most_popular_channel, log_generation};
+use crate::utils::visibility;
+
+/// How often the scheduler checks which guilds are due for a message.
+const TICK: Duration = Duration::from_secs(30);
+
+/// How long the first retry after a failed post waits, doubling on every retry.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// How many times a failed post is retried before waiting for the next interval.
+const MAX_RETRIES: u32 = 5;
+
+/// Counts the messages members sent in each channel since the bot last spoke there.
+#[derive(Default)]
+pub struct ChannelActivity {
+    counts: Mutex<HashMap<ChannelId, u64>>,
+}
+
+impl ChannelActivity {
+    /// Records a message from a member. Returns true, and starts counting over,
+    /// once `post_after` messages were sent, 0 never does.
+    pub fn record(&self, channel_id: ChannelId, post_after: u64) -> bool {
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(channel_id).or_insert(0);
+        *count += 1;
+
+        if post_after > 0 && *count >= post_after {
+            *count = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Starts counting over, since the bot just spoke in the channel.
+    pub fn reset(&self, channel_id: ChannelId) {
+        self.counts.lock().unwrap().remove(&channel_id);
+    }
+
+    /// Whether members spoke in the channel since the bot last did.
+    pub fn is_active(&self, channel_id: ChannelId) -> bool {
+        self.counts
+            .lock()
+            .unwrap()
+            .get(&channel_id)
+            .is_some_and(|count| *count > 0)
+    }
+}
+
+/// Whether the bot may post into a channel without being asked, following the
+/// guild's autopost settings and the channel's flags.
+pub fn may_post_unprompted(
+    settings: &GuildSettings,
+    flags: &ChannelFlags,
+    channel_id: ChannelId,
+) -> bool {
+    settings.autopost_enabled
+        && flags.post_into
+        && !settings.is_quiet_time(Utc::now())
+        && (settings.autopost_channels.is_empty()
+            || settings.autopost_channels.contains(&channel_id.get()))
+}
+
+/// When a guild is due next, and how many posts failed in a row.
+struct Schedule {
+    next_post: Instant,
+    failures: u32,
+}
+
+/// Spawns the background task that posts generated messages into every guild
+/// on a timer, following each guild's autopost settings.
+pub fn spawn(ctx: Context, database: Arc<Database>, activity: Arc<ChannelActivity>) {
+    tokio::spawn(async move {
+        let mut rng = OsRng;
+        let mut schedules: HashMap<GuildId, Schedule> = HashMap::new();
+
+        loop {
+            for guild_id in ctx.cache.guilds() {
+                // Guilds seen for the first time are due right away
+                let schedule = schedules.entry(guild_id).or_insert(Schedule {
+                    next_post: Instant::now(),
+                    failures: 0,
+                });
+
+                if schedule.next_post > Instant::now() {
+                    continue;
+                }
+
+                let settings = GuildSettings::load(database.as_ref(), guild_id.get()).await;
+
+                match post(&ctx, database.clone(), &activity, guild_id, &settings).await {
+                    Err(e) if schedule.failures < MAX_RETRIES => {
+                        schedule.failures += 1;
+                        let delay = RETRY_DELAY * 2u32.pow(schedule.failures - 1);
+                        eprintln!(
+                            "Failed to autopost in guild {}, retrying in {}s: {}",
+                            guild_id,
+                            delay.as_secs(),
+                            e
+                        );
+                        schedule.next_post = Instant::now() + delay;
+                        continue;
+                    }
+                    Err(e) => eprintln!(
+                        "Failed to autopost in guild {}, giving up until the next interval: {}",
+                        guild_id, e
+                    ),
+                    Ok(()) => {}
+                }
+
+                // Wait a random amount of seconds within the guild's interval
+                let (min_wait, max_wait) = settings.autopost_wait_range();
+                let wait = rng.gen_range(min_wait..=max_wait);
+                schedule.next_post = Instant::now() + Duration::from_secs(wait);
+                schedule.failures = 0;
+            }
+
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+/// Posts a generated message into the guild, unless it is paused, in its quiet
+/// hours, posts on activity instead, or has nowhere to post.
+async fn post(
+    ctx: &Context,
+    database: Arc<Database>,
+    activity: &ChannelActivity,
+    guild_id: GuildId,
+    settings: &GuildSettings,
+) -> Result<(), serenity::Error> {
+    // Activity-triggered guilds are posted into from the message handler
+    if !settings.autopost_enabled
+        || settings.autopost_after_messages > 0
+        || settings.is_quiet_time(Utc::now())
+    {
+        return Ok(());
+    }
+
+    // Only channels everyone can read, so private channels never leak into public ones
+    let mut channels = visibility::readable_by_everyone(&ctx.cache, guild_id);
+    if !settings.autopost_channels.is_empty() {
+        channels.retain(|channel_id| settings.autopost_channels.contains(channel_id));
+    }
+
+    let channel_id = match get_most_popular_channel(guild_id, &channels, database.clone()).await {
+        0 => return Ok(()),
+        channel_id => ChannelId::new(channel_id),
+    };
+
+    // Don't talk to an empty room, members must have spoken since the bot last did
+    if !activity.is_active(channel_id) {
+        return Ok(());
+    }
+
+    if let Some(generated) =
+        generate_markov_message(guild_id, channel_id, None, database.clone(), settings).await
+    {
+        let posted = channel_id
+            .send_message(&ctx.http, CreateMessage::new().content(&generated.content))
+            .await?;
+        log_generation(
+            database.as_ref(),
+            guild_id,
+            &posted,
+            channel_id,
+            &generated,
+            settings,
+        )
+        .await;
+    }
+
+    Ok(())
+}
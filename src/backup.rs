@@ -0,0 +1,214 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::database::Database;
+
+/// Directory the snapshots are written to, relative to the working directory.
+pub const BACKUP_DIRECTORY: &str = "backups";
+
+/// File in the backup directory naming the snapshot to restore on the next start.
+const RESTORE_REQUEST_FILE: &str = "restore-on-startup";
+
+/// How many snapshots are kept when `BACKUP_KEEP` is not set.
+const DEFAULT_BACKUP_KEEP: usize = 7;
+
+/// A snapshot in the backup directory.
+pub struct Snapshot {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Returns how many snapshots to keep, from `BACKUP_KEEP`. Zero disables scheduled backups.
+pub fn keep() -> usize {
+    match std::env::var("BACKUP_KEEP") {
+        Ok(value) if !value.is_empty() => value.parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Invalid BACKUP_KEEP `{}`, using {}",
+                value, DEFAULT_BACKUP_KEEP
+            );
+            DEFAULT_BACKUP_KEEP
+        }),
+        _ => DEFAULT_BACKUP_KEEP,
+    }
+}
+
+/// Returns the file a `sqlite:` database URL points at, or `None` for other backends.
+pub fn sqlite_path(database_url: &str) -> Option<PathBuf> {
+    let path = database_url
+        .strip_prefix("sqlite://")
+        .or_else(|| database_url.strip_prefix("sqlite:"))?;
+    let path = path.split('?').next().unwrap_or(path);
+
+    if path.is_empty() || path == ":memory:" {
+        return None;
+    }
+
+    Some(PathBuf::from(path))
+}
+
+/// Takes a snapshot of the running database, returning its path.
+pub async fn create(database: &Database) -> Result<PathBuf, String> {
+    fs::create_dir_all(BACKUP_DIRECTORY)
+        .map_err(|e| format!("Failed to create `{}`: {}", BACKUP_DIRECTORY, e))?;
+
+    // Milliseconds keep an on demand snapshot from colliding with the scheduled one
+    let path = PathBuf::from(BACKUP_DIRECTORY).join(format!(
+        "data-{}.db",
+        Utc::now().format("%Y%m%d-%H%M%S-%3f")
+    ));
+
+    database
+        .backup(&path)
+        .await
+        .map_err(|e| format!("Failed to back up database: {}", e))?;
+
+    Ok(path)
+}
+
+/// Lists the snapshots in the backup directory, oldest first.
+pub fn list() -> Result<Vec<Snapshot>, String> {
+    let entries = match fs::read_dir(BACKUP_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read `{}`: {}", BACKUP_DIRECTORY, e)),
+    };
+
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with("data-") || !name.ends_with(".db") {
+                return None;
+            }
+
+            let size = entry.metadata().ok()?.len();
+            Some(Snapshot { name, size })
+        })
+        .collect();
+
+    // Names embed the timestamp, so they sort chronologically
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(snapshots)
+}
+
+/// Deletes all but the newest `keep` snapshots, returning how many were deleted.
+pub fn rotate(keep: usize) -> Result<usize, String> {
+    let snapshots = list()?;
+    let excess = snapshots.len().saturating_sub(keep);
+
+    for snapshot in &snapshots[..excess] {
+        let path = Path::new(BACKUP_DIRECTORY).join(&snapshot.name);
+        fs::remove_file(&path)
+            .map_err(|e| format!("Failed to delete `{}`: {}", path.display(), e))?;
+    }
+
+    Ok(excess)
+}
+
+/// Resolves a snapshot name to its path, rejecting anything outside the backup directory.
+fn snapshot_path(name: &str) -> Result<PathBuf, String> {
+    if Path::new(name).file_name().and_then(|n| n.to_str()) != Some(name) {
+        return Err(format!("`{}` is not a valid snapshot name.", name));
+    }
+
+    let path = Path::new(BACKUP_DIRECTORY).join(name);
+    if !path.is_file() {
+        return Err(format!("Snapshot `{}` does not exist.", name));
+    }
+
+    Ok(path)
+}
+
+/// Replaces the SQLite database at `database_url` with the snapshot `name`.
+///
+/// Must run before the database is opened. The replaced files are kept next
+/// to it with a timestamped `.pre-restore` suffix, and are moved back if the
+/// snapshot can't be copied in.
+pub fn restore(name: &str, database_url: &str) -> Result<(), String> {
+    let target = sqlite_path(database_url)
+        .ok_or_else(|| "Backups can only be restored into a SQLite database.".to_string())?;
+    let snapshot = snapshot_path(name)?;
+
+    let suffix = format!(".pre-restore-{}", Utc::now().format("%Y%m%d-%H%M%S"));
+    let files: Vec<(PathBuf, PathBuf)> = ["", "-wal", "-shm"]
+        .iter()
+        .map(|extension| {
+            let path = PathBuf::from(format!("{}{}", target.display(), extension));
+            let aside = PathBuf::from(format!("{}{}", path.display(), suffix));
+            (path, aside)
+        })
+        .filter(|(path, _)| path.exists())
+        .collect();
+
+    // Never overwrite the files an earlier restore moved aside
+    if let Some((_, aside)) = files.iter().find(|(_, aside)| aside.exists()) {
+        return Err(format!(
+            "`{}` already exists, not restoring over it.",
+            aside.display()
+        ));
+    }
+
+    // Move the current database and its WAL files out of the way
+    let mut moved = Vec::new();
+    for (path, aside) in &files {
+        if let Err(e) = fs::rename(path, aside) {
+            move_back(&moved);
+            return Err(format!("Failed to move `{}` aside: {}", path.display(), e));
+        }
+        moved.push((path.clone(), aside.clone()));
+    }
+
+    if let Err(e) = fs::copy(&snapshot, &target) {
+        let _ = fs::remove_file(&target);
+        move_back(&moved);
+        return Err(format!(
+            "Failed to copy `{}` to `{}`, kept the current database: {}",
+            snapshot.display(),
+            target.display(),
+            e
+        ));
+    }
+
+    Ok(())
+}
+
+/// Puts the files moved aside by `restore` back in place.
+fn move_back(moved: &[(PathBuf, PathBuf)]) {
+    for (path, aside) in moved {
+        if let Err(e) = fs::rename(aside, path) {
+            eprintln!(
+                "Failed to move `{}` back to `{}`: {}",
+                aside.display(),
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Marks the snapshot `name` to be restored the next time the bot starts.
+pub fn schedule_restore(name: &str) -> Result<(), String> {
+    snapshot_path(name)?;
+
+    fs::write(Path::new(BACKUP_DIRECTORY).join(RESTORE_REQUEST_FILE), name)
+        .map_err(|e| format!("Failed to schedule restore: {}", e))
+}
+
+/// Restores the snapshot scheduled with `schedule_restore`, if any, returning its name.
+pub fn restore_scheduled(database_url: &str) -> Result<Option<String>, String> {
+    let request = Path::new(BACKUP_DIRECTORY).join(RESTORE_REQUEST_FILE);
+    let name = match fs::read_to_string(&request) {
+        Ok(name) => name.trim().to_string(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read `{}`: {}", request.display(), e)),
+    };
+
+    // Remove the request first, so a failing restore isn't retried on every start
+    fs::remove_file(&request)
+        .map_err(|e| format!("Failed to delete `{}`: {}", request.display(), e))?;
+
+    restore(&name, database_url)?;
+    Ok(Some(name))
+}
@@ -0,0 +1,342 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serenity::all::{GuildId, GuildPagination, Http};
+
+use crate::backup;
+use crate::database::{self, Database, MessageFilter};
+use crate::utils::export::{file_name, write_messages, ExportFormat, EXPORT_DIRECTORY};
+use crate::utils::snowflake;
+
+const USAGE: &str = "\
+Usage: markov-bot [COMMAND]
+
+Runs the bot when no command is given.
+
+Commands:
+  export <guild_id> [--format jsonl|csv] [--channel <id>] [--user <id>]
+         [--since YYYY-MM-DD] [--until YYYY-MM-DD] [--output <path>|-]
+      Dumps a guild's stored messages. Writes to the exports directory by default,
+      or to stdout when the output is `-`.
+  guilds orphans
+      Lists guilds with stored data that the bot is no longer in.
+  guilds purge <guild_id>... | --orphans [--dry-run] [--yes]
+      Deletes the stored data of the given guilds, or of every orphaned guild.
+      Orphans are listed and confirmed first, `--dry-run` only lists them and
+      `--yes` skips the confirmation.
+  backup create
+      Takes a snapshot of the SQLite database into the backups directory, even
+      while the bot is running.
+  backup list
+      Lists the snapshots in the backups directory.
+  backup restore <name>
+      Replaces the SQLite database with a snapshot the next time the bot starts.";
+
+/// Runs the subcommand in `args` (without the binary name).
+pub async fn run(args: &[String], database_url: &str) -> Result<(), String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err(USAGE.into()),
+    };
+
+    match command {
+        "export" => export(rest, connect(database_url).await?).await,
+        "guilds" => guilds(rest, connect(database_url).await?).await,
+        "backup" => backup(rest, database_url).await,
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Unknown command `{}`.\n\n{}", command, USAGE)),
+    }
+}
+
+async fn connect(database_url: &str) -> Result<Arc<Database>, String> {
+    database::connect(database_url)
+        .await
+        .map_err(|e| format!("Failed to initialize database: {}", e))
+}
+
+/// Splits `args` into positional arguments and `--flag value` pairs.
+fn parse_flags(args: &[String]) -> Result<(Vec<&str>, HashMap<&str, &str>), String> {
+    parse_flags_with_switches(args, &[])
+}
+
+/// Like `parse_flags`, but the flags in `switches` take no value.
+fn parse_flags_with_switches<'a>(
+    args: &'a [String],
+    switches: &[&str],
+) -> Result<(Vec<&'a str>, HashMap<&'a str, &'a str>), String> {
+    let mut positional = Vec::new();
+    let mut flags = HashMap::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(flag) if switches.contains(&flag) => {
+                flags.insert(flag, "");
+            }
+            Some(flag) => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for `--{}`.", flag))?;
+                flags.insert(flag, value.as_str());
+            }
+            None => positional.push(arg.as_str()),
+        }
+    }
+
+    Ok((positional, flags))
+}
+
+fn parse_id(value: &str, what: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid {}.", value, what))
+}
+
+async fn export(args: &[String], database: Arc<Database>) -> Result<(), String> {
+    let (positional, flags) = parse_flags(args)?;
+
+    let guild_id = match positional.first() {
+        Some(guild_id) => parse_id(guild_id, "guild id")?,
+        None => return Err(USAGE.into()),
+    };
+
+    let format = match flags.get("format") {
+        Some(format) => ExportFormat::parse(format)
+            .ok_or_else(|| format!("`{}` is not a supported format.", format))?,
+        None => ExportFormat::Jsonl,
+    };
+
+    let filter = MessageFilter {
+        guild_id,
+        channel_id: flags
+            .get("channel")
+            .map(|id| parse_id(id, "channel id"))
+            .transpose()?,
+        author_id: flags
+            .get("user")
+            .map(|id| parse_id(id, "user id"))
+            .transpose()?,
+        since: flags
+            .get("since")
+            .map(|date| {
+                snowflake::from_date(date).ok_or_else(|| format!("`{}` is not a valid date.", date))
+            })
+            .transpose()?,
+        until: flags
+            .get("until")
+            .map(|date| {
+                snowflake::after_date(date)
+                    .ok_or_else(|| format!("`{}` is not a valid date.", date))
+            })
+            .transpose()?,
+    };
+
+    if flags.get("output") == Some(&"-") {
+        let stdout = BufWriter::new(io::stdout().lock());
+        let (mut stdout, _) = write_messages(database.as_ref(), &filter, format, stdout)
+            .await
+            .map_err(|e| format!("Failed to export messages: {}", e))?;
+        return stdout.flush().map_err(|e| e.to_string());
+    }
+
+    let path = match flags.get("output") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(EXPORT_DIRECTORY).join(file_name(&filter, format)),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let file = std::fs::File::create(&path)
+        .map_err(|e| format!("Failed to create `{}`: {}", path.display(), e))?;
+    let (mut file, rows) = write_messages(database.as_ref(), &filter, format, BufWriter::new(file))
+        .await
+        .map_err(|e| format!("Failed to export messages: {}", e))?;
+    file.flush().map_err(|e| e.to_string())?;
+
+    println!("Exported {} messages to {}", rows, path.display());
+    Ok(())
+}
+
+/// Fetches the ids of every guild the bot is currently in.
+async fn current_guilds() -> Result<HashSet<u64>, String> {
+    let token = std::env::var("DISCORD_TOKEN")
+        .map_err(|_| "Expected DISCORD_TOKEN to be defined in environment.".to_string())?;
+    let http = Http::new(&token);
+
+    let mut guild_ids = HashSet::new();
+    let mut after: Option<GuildId> = None;
+
+    // The endpoint returns at most 200 guilds per page
+    loop {
+        let page = http
+            .get_guilds(after.map(GuildPagination::After), Some(200))
+            .await
+            .map_err(|e| format!("Failed to fetch guilds from Discord: {}", e))?;
+
+        guild_ids.extend(page.iter().map(|guild| guild.id.get()));
+
+        match page.last() {
+            Some(guild) if page.len() == 200 => after = Some(guild.id),
+            _ => return Ok(guild_ids),
+        }
+    }
+}
+
+/// Returns the stored guilds the bot is no longer in, with their message counts.
+async fn orphaned_guilds(database: &Database) -> Result<Vec<(u64, u64)>, String> {
+    let current = current_guilds().await?;
+
+    // An empty list means the token belongs to another bot or Discord hasn't
+    // caught up yet, rather than every stored guild being orphaned
+    if current.is_empty() {
+        return Err(
+            "Discord reports the bot isn't in any guild, refusing to treat every stored guild as orphaned. Check DISCORD_TOKEN, or purge guilds by id."
+                .into(),
+        );
+    }
+
+    let stored = database
+        .get_stored_guilds()
+        .await
+        .map_err(|e| format!("Failed to fetch stored guilds: {}", e))?;
+
+    Ok(stored
+        .into_iter()
+        .filter(|(guild_id, _)| !current.contains(guild_id))
+        .collect())
+}
+
+async fn guilds(args: &[String], database: Arc<Database>) -> Result<(), String> {
+    let (positional, flags) = parse_flags_with_switches(args, &["orphans", "dry-run", "yes"])?;
+
+    match positional.split_first() {
+        Some((&"orphans", _)) => {
+            let orphans = orphaned_guilds(database.as_ref()).await?;
+            let pending: HashMap<u64, i64> = database
+                .get_pending_guild_purges()
+                .await
+                .map_err(|e| format!("Failed to fetch pending purges: {}", e))?
+                .into_iter()
+                .collect();
+
+            if orphans.is_empty() {
+                println!("No orphaned guilds.");
+                return Ok(());
+            }
+
+            for (guild_id, messages) in orphans {
+                let purge = match pending.get(&guild_id) {
+                    Some(purge_after) => match chrono::DateTime::from_timestamp(*purge_after, 0) {
+                        Some(time) => format!("purge scheduled after {}", time),
+                        None => "purge scheduled".into(),
+                    },
+                    None => "no purge scheduled".into(),
+                };
+                println!("{}\t{} messages\t{}", guild_id, messages, purge);
+            }
+
+            Ok(())
+        }
+        Some((&"purge", guild_ids)) => {
+            let guild_ids: Vec<u64> = if flags.contains_key("orphans") {
+                let orphans = orphaned_guilds(database.as_ref()).await?;
+                if orphans.is_empty() {
+                    println!("No orphaned guilds.");
+                    return Ok(());
+                }
+
+                for (guild_id, messages) in &orphans {
+                    println!("{}\t{} messages", guild_id, messages);
+                }
+
+                let messages: u64 = orphans.iter().map(|(_, messages)| messages).sum();
+                let summary = format!("{} messages from {} guilds", messages, orphans.len());
+                if flags.contains_key("dry-run") {
+                    println!("Would purge {}", summary);
+                    return Ok(());
+                }
+
+                if !flags.contains_key("yes") && !confirm(&format!("Purge {}?", summary))? {
+                    println!("Aborted.");
+                    return Ok(());
+                }
+
+                orphans.into_iter().map(|(guild_id, _)| guild_id).collect()
+            } else if guild_ids.is_empty() {
+                return Err(USAGE.into());
+            } else {
+                guild_ids
+                    .iter()
+                    .map(|id| parse_id(id, "guild id"))
+                    .collect::<Result<_, _>>()?
+            };
+
+            for guild_id in guild_ids {
+                let purged = database
+                    .purge_guild(guild_id)
+                    .await
+                    .map_err(|e| format!("Failed to purge guild {}: {}", guild_id, e))?;
+                println!("Purged {} messages from guild {}", purged, guild_id);
+            }
+
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> Result<bool, String> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush().map_err(|e| e.to_string())?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| e.to_string())?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+async fn backup(args: &[String], database_url: &str) -> Result<(), String> {
+    let (positional, _) = parse_flags(args)?;
+
+    match positional.as_slice() {
+        ["create"] => {
+            let database = connect(database_url).await?;
+            let path = backup::create(database.as_ref()).await?;
+            println!("Backed up the database to {}", path.display());
+            Ok(())
+        }
+        ["list"] => {
+            let snapshots = backup::list()?;
+            if snapshots.is_empty() {
+                println!("No snapshots in {}.", backup::BACKUP_DIRECTORY);
+            }
+
+            for snapshot in snapshots {
+                println!("{}\t{} bytes", snapshot.name, snapshot.size);
+            }
+
+            Ok(())
+        }
+        ["restore", name] => {
+            // The bot may be running on the database, so its files are only
+            // replaced when it starts next, before the database is opened
+            if backup::sqlite_path(database_url).is_none() {
+                return Err("Backups can only be restored into a SQLite database.".into());
+            }
+            backup::schedule_restore(name)?;
+            println!("{} will be restored the next time the bot starts.", name);
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
+}
@@ -0,0 +1,155 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, CreateEmbed,
+    CreateEmbedFooter, EditInteractionResponse, Permissions, ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::settings::GuildSettings;
+use crate::state::AppState;
+
+pub struct Autopost;
+
+#[async_trait]
+impl Command for Autopost {
+    fn name(&self) -> &'static str {
+        "autopost"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s.get(),
+            _ => return Ok(()),
+        };
+
+        let subcommand = match command.data.options().first() {
+            Some(ResolvedOption {
+                name,
+                value: ResolvedValue::SubCommand(_),
+                ..
+            }) => *name,
+            _ => return Ok(()),
+        };
+
+        let (enabled, content) = match subcommand {
+            "status" => {
+                let settings = GuildSettings::load(state.database.as_ref(), guild_id).await;
+                command
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new().embed(status(&settings)),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            "pause" => (
+                false,
+                "Automatic messages are paused, use `/autopost resume` to start them again.",
+            ),
+            "resume" => (
+                true,
+                "Automatic messages are back on and resume at the next scheduled time.",
+            ),
+            _ => return Ok(()),
+        };
+
+        let content = match state
+            .database
+            .set_guild_setting(guild_id, "autopost_enabled", &enabled.to_string())
+            .await
+        {
+            Ok(_) => {
+                state.lookups.forget_settings(guild_id);
+                content
+            }
+            Err(e) => {
+                eprintln!("Failed to set guild setting: {}", e);
+                "Something went wrong, please try again later."
+            }
+        };
+
+        command
+            .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+            .await?;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("autopost")
+            .description("Pause, resume or check the automatic messages.")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "status",
+                "Show the schedule of automatic messages",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "pause",
+                "Stop posting automatic messages",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "resume",
+                "Start posting automatic messages again",
+            ))
+    }
+}
+
+/// Summarizes the guild's autopost schedule.
+fn status(settings: &GuildSettings) -> CreateEmbed {
+    let (min_wait, max_wait) = settings.autopost_wait_range();
+
+    let channels = if settings.autopost_channels.is_empty() {
+        "the most active channel".to_string()
+    } else {
+        settings
+            .autopost_channels
+            .iter()
+            .map(|channel_id| format!("<#{}>", channel_id))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let quiet_hours = if settings.autopost_quiet_start_hour == settings.autopost_quiet_end_hour {
+        "none".to_string()
+    } else {
+        format!(
+            "{:02}:00 to {:02}:00 ({})",
+            settings.autopost_quiet_start_hour,
+            settings.autopost_quiet_end_hour,
+            settings.timezone.name()
+        )
+    };
+
+    CreateEmbed::new()
+        .title("Automatic Messages")
+        .field(
+            "Status",
+            if settings.autopost_enabled {
+                "running"
+            } else {
+                "paused"
+            },
+            true,
+        )
+        .field(
+            "Wait",
+            format!("{} to {} seconds", min_wait, max_wait),
+            true,
+        )
+        .field("Channels", channels, false)
+        .field("Quiet hours", quiet_hours, false)
+        .footer(CreateEmbedFooter::new("Change the schedule with /config"))
+        .color(0x5865F2)
+}
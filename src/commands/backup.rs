@@ -0,0 +1,160 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption,
+    EditInteractionResponse, Permissions, ResolvedOption, ResolvedValue, UserId,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::backup;
+use crate::commands::Command;
+use crate::state::AppState;
+
+/// Maximum amount of snapshots listed in a single response.
+const MAX_LISTED_SNAPSHOTS: usize = 20;
+
+pub struct Backup;
+
+#[async_trait]
+impl Command for Backup {
+    fn name(&self) -> &'static str {
+        "backup"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        if !is_owner(ctx, command.user.id).await? {
+            command
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .content("Only the bot owner can manage backups."),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let options = &command.data.options();
+
+        let (subcommand, options) = match options.first() {
+            Some(ResolvedOption {
+                name,
+                value: ResolvedValue::SubCommand(options),
+                ..
+            }) => (*name, options),
+            _ => return Ok(()),
+        };
+
+        let content = match subcommand {
+            "create" => match backup::create(state.database.as_ref()).await {
+                Ok(path) => {
+                    if let Err(e) = backup::rotate(backup::keep().max(1)) {
+                        eprintln!("Failed to rotate backups: {}", e);
+                    }
+                    format!("Backed up the database to `{}`.", path.display())
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    "Failed to back up the database, check the logs for details.".into()
+                }
+            },
+            "list" => match backup::list() {
+                Ok(snapshots) if snapshots.is_empty() => "There are no backups yet.".into(),
+                Ok(snapshots) => {
+                    let lines: Vec<String> = snapshots
+                        .iter()
+                        .rev()
+                        .take(MAX_LISTED_SNAPSHOTS)
+                        .map(|snapshot| {
+                            format!(
+                                "`{}` ({:.1} MiB)",
+                                snapshot.name,
+                                snapshot.size as f64 / (1024.0 * 1024.0)
+                            )
+                        })
+                        .collect();
+                    format!("**Backups, newest first:**\n{}", lines.join("\n"))
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    "Failed to list backups.".into()
+                }
+            },
+            "restore" => {
+                let name = options
+                    .iter()
+                    .find(|opt| opt.name == "name")
+                    .and_then(|opt| {
+                        if let ResolvedValue::String(s) = &opt.value {
+                            Some(*s)
+                        } else {
+                            None
+                        }
+                    })
+                    .unwrap_or_default();
+
+                match backup::schedule_restore(name) {
+                    Ok(_) => format!(
+                        "`{}` will be restored the next time the bot starts. Restart it to apply.",
+                        name
+                    ),
+                    Err(e) => e,
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        command
+            .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+            .await?;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("backup")
+            .description("Manage database backups (bot owner only)")
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "create",
+                "Take a snapshot of the database now",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "list",
+                "List the available snapshots",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "restore",
+                    "Restore a snapshot the next time the bot starts",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "name",
+                        "Snapshot name, as shown by /backup list",
+                    )
+                    .required(true),
+                ),
+            )
+    }
+}
+
+/// Checks whether `user_id` owns the application, or is on the team that does.
+async fn is_owner(ctx: &Context, user_id: UserId) -> Result<bool, Error> {
+    let info = ctx.http.get_current_application_info().await?;
+
+    if let Some(team) = info.team {
+        return Ok(team.members.iter().any(|member| member.user.id == user_id));
+    }
+
+    Ok(info.owner.is_some_and(|owner| owner.id == user_id))
+}
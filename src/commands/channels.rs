@@ -0,0 +1,288 @@
+use serenity::all::{
+    ChannelId, ChannelType, CommandInteraction, CommandOptionType, CreateCommand,
+    CreateCommandOption, CreateEmbed, EditInteractionResponse, GuildId, Permissions,
+    ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::database::{ChannelFlags, Database};
+use crate::state::AppState;
+use crate::utils::visibility;
+
+pub struct Channels;
+
+#[async_trait]
+impl Command for Channels {
+    fn name(&self) -> &'static str {
+        "channels"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s.get(),
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        let (subcommand, options) = match options.first() {
+            Some(ResolvedOption {
+                name,
+                value: ResolvedValue::SubCommand(options),
+                ..
+            }) => (*name, options),
+            _ => return Ok(()),
+        };
+
+        let channel_id = options
+            .iter()
+            .find(|opt| opt.name == "channel")
+            .and_then(|opt| {
+                if let ResolvedValue::Channel(channel) = &opt.value {
+                    Some(channel.id)
+                } else {
+                    None
+                }
+            });
+
+        let bool_option = |name: &str| {
+            options.iter().find(|opt| opt.name == name).and_then(|opt| {
+                if let ResolvedValue::Boolean(b) = &opt.value {
+                    Some(*b)
+                } else {
+                    None
+                }
+            })
+        };
+
+        let (channel_id, flags) = match (subcommand, channel_id) {
+            ("list", _) => return list(ctx, command, state.database.as_ref(), guild_id).await,
+            ("reset", Some(channel_id)) => {
+                return reset(ctx, command, state, guild_id, channel_id).await
+            }
+            ("ignore", Some(channel_id)) => (channel_id, ChannelFlags::IGNORE),
+            ("set", Some(channel_id)) => {
+                let current = match state
+                    .database
+                    .get_channel_flags(
+                        guild_id,
+                        channel_id.get(),
+                        visibility::thread_parent(&ctx.cache, GuildId::new(guild_id), channel_id),
+                    )
+                    .await
+                {
+                    Ok(flags) => flags,
+                    Err(e) => {
+                        eprintln!("Failed to get channel flags: {}", e);
+                        return database_error(ctx, command).await;
+                    }
+                };
+
+                // Only the given flags change, the rest keep their current value
+                let flags = ChannelFlags {
+                    collect: bool_option("collect").unwrap_or(current.collect),
+                    generate_from: bool_option("generate_from").unwrap_or(current.generate_from),
+                    post_into: bool_option("post_into").unwrap_or(current.post_into),
+                };
+
+                (channel_id, flags)
+            }
+            _ => return Ok(()),
+        };
+
+        if let Err(e) = state
+            .database
+            .set_channel_flags(guild_id, channel_id.get(), flags)
+            .await
+        {
+            eprintln!("Failed to set channel flags: {}", e);
+            return database_error(ctx, command).await;
+        }
+        state.lookups.forget_channel_flags(guild_id);
+
+        command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new().content(format!(
+                    "<#{}>: {}",
+                    channel_id,
+                    describe(&flags)
+                )),
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("channels")
+            .description("Choose what the bot may do in each channel.")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "list",
+                "Show the channels with custom settings",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "set",
+                    "Allow or deny collecting, generating from and posting into a channel",
+                )
+                .add_sub_option(channel_option("The channel to change"))
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "collect",
+                    "Store new messages sent in this channel",
+                ))
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "generate_from",
+                    "Use this channel's messages for generated text and /guess",
+                ))
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "post_into",
+                    "Let automatic messages be posted here",
+                )),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "ignore",
+                    "Never read from or post into a channel",
+                )
+                .add_sub_option(channel_option("The channel to ignore")),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "reset",
+                    "Go back to the defaults for a channel",
+                )
+                .add_sub_option(channel_option("The channel to reset")),
+            )
+    }
+}
+
+async fn list(
+    ctx: &Context,
+    command: &CommandInteraction,
+    database: &Database,
+    guild_id: u64,
+) -> Result<(), Error> {
+    let channels = match database.get_guild_channel_flags(guild_id).await {
+        Ok(channels) => channels,
+        Err(e) => {
+            eprintln!("Failed to get channel flags: {}", e);
+            return database_error(ctx, command).await;
+        }
+    };
+
+    let description = if channels.is_empty() {
+        "No channels are configured, the bot reads and posts everywhere it can.".into()
+    } else {
+        channels
+            .iter()
+            .map(|(channel_id, flags)| format!("<#{}>: {}", channel_id, describe(flags)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().embed(
+                CreateEmbed::new()
+                    .title("Channel Settings")
+                    .description(description)
+                    .color(0x5865F2),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn reset(
+    ctx: &Context,
+    command: &CommandInteraction,
+    state: &AppState,
+    guild_id: u64,
+    channel_id: ChannelId,
+) -> Result<(), Error> {
+    let content = match state
+        .database
+        .reset_channel_flags(guild_id, channel_id.get())
+        .await
+    {
+        Ok(true) => {
+            state.lookups.forget_channel_flags(guild_id);
+            format!(
+                "<#{}> is back to the defaults: {}",
+                channel_id,
+                describe(&ChannelFlags::default())
+            )
+        }
+        Ok(false) => format!("<#{}> was not configured.", channel_id),
+        Err(e) => {
+            eprintln!("Failed to reset channel flags: {}", e);
+            return database_error(ctx, command).await;
+        }
+    };
+
+    command
+        .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+        .await?;
+    Ok(())
+}
+
+/// Lists what the bot may do in a channel, in words.
+fn describe(flags: &ChannelFlags) -> String {
+    if *flags == ChannelFlags::IGNORE {
+        return "ignored".into();
+    }
+
+    let allowed: Vec<&str> = [
+        (flags.collect, "collect"),
+        (flags.generate_from, "generate from"),
+        (flags.post_into, "post into"),
+    ]
+    .iter()
+    .filter(|(allowed, _)| *allowed)
+    .map(|(_, name)| *name)
+    .collect();
+
+    allowed.join(", ")
+}
+
+async fn database_error(ctx: &Context, command: &CommandInteraction) -> Result<(), Error> {
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().content("Something went wrong, please try again later."),
+        )
+        .await?;
+    Ok(())
+}
+
+fn channel_option(description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::Channel, "channel", description)
+        .channel_types(vec![
+            ChannelType::Text,
+            ChannelType::News,
+            ChannelType::PublicThread,
+            ChannelType::PrivateThread,
+            ChannelType::NewsThread,
+        ])
+        .required(true)
+}
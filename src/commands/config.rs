@@ -0,0 +1,341 @@
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    EditInteractionResponse, GuildId, Permissions, ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::{self, Command};
+use crate::settings::{self, SETTINGS};
+use crate::state::AppState;
+
+/// Longest a page of `/config get` may get, under Discord's 4096 character
+/// limit for embed descriptions.
+const MAX_PAGE_LENGTH: usize = 4000;
+
+pub struct Config;
+
+#[async_trait]
+impl Command for Config {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s.get(),
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        let (subcommand, options) = match options.first() {
+            Some(ResolvedOption {
+                name,
+                value: ResolvedValue::SubCommand(options),
+                ..
+            }) => (*name, options),
+            _ => return Ok(()),
+        };
+
+        let string_option = |name: &str| {
+            options.iter().find(|opt| opt.name == name).and_then(|opt| {
+                if let ResolvedValue::String(s) = &opt.value {
+                    Some(*s)
+                } else {
+                    None
+                }
+            })
+        };
+
+        // Validate the key up front, all subcommands take one
+        let setting = match string_option("key") {
+            Some(key) => match settings::find(key) {
+                Some(setting) => Some(setting),
+                None => {
+                    let keys: Vec<String> =
+                        SETTINGS.iter().map(|s| format!("`{}`", s.key)).collect();
+                    let content = format!(
+                        "Unknown setting `{}`. Available settings: {}",
+                        key,
+                        keys.join(", ")
+                    );
+                    command
+                        .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+                        .await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        let builder = match subcommand {
+            "get" => {
+                let values = match state.database.get_guild_settings(guild_id).await {
+                    Ok(values) => values,
+                    Err(e) => {
+                        eprintln!("Failed to get guild settings: {}", e);
+                        return database_error(ctx, command).await;
+                    }
+                };
+
+                let shown: Vec<_> = match setting {
+                    Some(setting) => vec![setting],
+                    None => SETTINGS.iter().collect(),
+                };
+
+                let entries: Vec<String> = shown
+                    .into_iter()
+                    .map(|setting| {
+                        let value = match values.get(setting.key) {
+                            Some(value) => format!("`{}`", value),
+                            None => format!("`{}` (default)", setting.default),
+                        };
+
+                        format!("**{}**: {}\n{}", setting.key, value, setting.description)
+                    })
+                    .collect();
+
+                return show_pages(ctx, command, &paginate(&entries)).await;
+            }
+            "set" => {
+                let (setting, value) = match (setting, string_option("value")) {
+                    (Some(setting), Some(value)) => (setting, value),
+                    _ => return Ok(()),
+                };
+
+                let value = match setting.parse(value) {
+                    Ok(value) => value,
+                    Err(reason) => {
+                        command
+                            .edit_response(
+                                &ctx.http,
+                                EditInteractionResponse::new().content(reason),
+                            )
+                            .await?;
+                        return Ok(());
+                    }
+                };
+
+                if let Err(e) = state
+                    .database
+                    .set_guild_setting(guild_id, setting.key, &value)
+                    .await
+                {
+                    eprintln!("Failed to set guild setting: {}", e);
+                    return database_error(ctx, command).await;
+                }
+                state.lookups.forget_settings(guild_id);
+
+                EditInteractionResponse::new()
+                    .content(format!("Set **{}** to `{}`.", setting.key, value))
+            }
+            "reset" => {
+                if let Err(e) = state
+                    .database
+                    .reset_guild_settings(guild_id, setting.map(|setting| setting.key))
+                    .await
+                {
+                    eprintln!("Failed to reset guild settings: {}", e);
+                    return database_error(ctx, command).await;
+                }
+                state.lookups.forget_settings(guild_id);
+
+                match setting {
+                    Some(setting) => EditInteractionResponse::new().content(format!(
+                        "Reset **{}** to its default, `{}`.",
+                        setting.key, setting.default
+                    )),
+                    None => EditInteractionResponse::new()
+                        .content("Reset every setting to its default."),
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        command.edit_response(&ctx.http, builder).await?;
+
+        // Guild commands leave disabled ones out, register them again
+        let toggled = match setting {
+            Some(setting) => setting.key == "disabled_commands" && subcommand != "get",
+            None => subcommand == "reset",
+        };
+        if toggled {
+            commands::register_guild_commands(&ctx.http, state, GuildId::new(guild_id)).await;
+        }
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("config")
+            .description("View or change this server's settings.")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "get",
+                    "Show the current settings",
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "key",
+                    "Only show this setting",
+                )),
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Change a setting")
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "key",
+                            "The setting to change",
+                        )
+                        .required(true),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "value",
+                            "The new value",
+                        )
+                        .required(true),
+                    ),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "reset",
+                    "Reset a setting, or all of them, to the default",
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "key",
+                    "The setting to reset, leave empty to reset everything",
+                )),
+            )
+    }
+}
+
+/// Groups the settings into embed descriptions that each fit Discord's limit.
+fn paginate(entries: &[String]) -> Vec<String> {
+    let mut pages: Vec<String> = Vec::new();
+
+    for entry in entries {
+        match pages.last_mut() {
+            Some(page) if page.len() + entry.len() + 2 <= MAX_PAGE_LENGTH => {
+                page.push_str("\n\n");
+                page.push_str(entry);
+            }
+            _ => pages.push(entry.clone()),
+        }
+    }
+
+    pages
+}
+
+/// Shows the settings, with buttons to flip through them if they take more than one page.
+async fn show_pages(
+    ctx: &Context,
+    command: &CommandInteraction,
+    pages: &[String],
+) -> Result<(), Error> {
+    let embed = |page: usize| {
+        let embed = CreateEmbed::new()
+            .title("Server Settings")
+            .description(&pages[page])
+            .color(0x5865F2);
+        if pages.len() > 1 {
+            embed.footer(CreateEmbedFooter::new(format!(
+                "Page {} of {}",
+                page + 1,
+                pages.len()
+            )))
+        } else {
+            embed
+        }
+    };
+
+    if pages.len() <= 1 {
+        command
+            .edit_response(&ctx.http, EditInteractionResponse::new().embed(embed(0)))
+            .await?;
+        return Ok(());
+    }
+
+    let mut page = 0;
+
+    loop {
+        let message = command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new()
+                    .embed(embed(page))
+                    .button(
+                        CreateButton::new("previous")
+                            .style(ButtonStyle::Secondary)
+                            .label("Previous")
+                            .disabled(page == 0),
+                    )
+                    .button(
+                        CreateButton::new("next")
+                            .style(ButtonStyle::Secondary)
+                            .label("Next")
+                            .disabled(page + 1 >= pages.len()),
+                    ),
+            )
+            .await?;
+
+        let interaction = match message
+            .await_component_interaction(&ctx.shard)
+            .author_id(command.user.id)
+            .timeout(Duration::from_secs(120))
+            .await
+        {
+            Some(x) => x,
+            None => {
+                // Stop paginating, but keep the last page visible
+                command
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .embed(embed(page))
+                            .components(vec![]),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        match interaction.data.custom_id.as_str() {
+            "previous" => page = page.saturating_sub(1),
+            "next" => page = (page + 1).min(pages.len() - 1),
+            _ => {}
+        }
+    }
+}
+
+async fn database_error(ctx: &Context, command: &CommandInteraction) -> Result<(), Error> {
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().content("Something went wrong, please try again later."),
+        )
+        .await?;
+    Ok(())
+}
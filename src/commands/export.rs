@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use serenity::all::{
+    CommandInteraction, CommandOptionType, CreateAttachment, CreateCommand, CreateCommandOption,
+    EditInteractionResponse, Permissions, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::database::MessageFilter;
+use crate::state::AppState;
+use crate::utils::export::{
+    export_messages, file_name, ExportFormat, ExportOutput, EXPORT_DIRECTORY,
+};
+use crate::utils::snowflake;
+
+pub struct Export;
+
+#[async_trait]
+impl Command for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        let format = options
+            .iter()
+            .find(|opt| opt.name == "format")
+            .and_then(|opt| {
+                if let ResolvedValue::String(s) = &opt.value {
+                    ExportFormat::parse(s)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(ExportFormat::Jsonl);
+
+        let channel_id = options
+            .iter()
+            .find(|opt| opt.name == "channel")
+            .and_then(|opt| {
+                if let ResolvedValue::Channel(channel) = &opt.value {
+                    Some(channel.id.get())
+                } else {
+                    None
+                }
+            });
+
+        let author_id = options
+            .iter()
+            .find(|opt| opt.name == "user")
+            .and_then(|opt| {
+                if let ResolvedValue::User(user, _) = &opt.value {
+                    Some(user.id.get())
+                } else {
+                    None
+                }
+            });
+
+        let date_option = |name: &str| {
+            options.iter().find(|opt| opt.name == name).and_then(|opt| {
+                if let ResolvedValue::String(s) = &opt.value {
+                    Some(*s)
+                } else {
+                    None
+                }
+            })
+        };
+
+        let since = match date_option("since") {
+            Some(date) => match snowflake::from_date(date) {
+                Some(since) => Some(since),
+                None => return invalid_date(ctx, command, date).await,
+            },
+            None => None,
+        };
+
+        let until = match date_option("until") {
+            Some(date) => match snowflake::after_date(date) {
+                Some(until) => Some(until),
+                None => return invalid_date(ctx, command, date).await,
+            },
+            None => None,
+        };
+
+        let filter = MessageFilter {
+            guild_id: guild_id.get(),
+            channel_id,
+            author_id,
+            since,
+            until,
+        };
+
+        let builder = match export_messages(state.database.as_ref(), &filter, format, Path::new(EXPORT_DIRECTORY))
+            .await
+        {
+            Ok((_, 0)) => EditInteractionResponse::new().content("No messages matched the given filters."),
+            Ok((ExportOutput::Memory(data), rows)) => EditInteractionResponse::new()
+                .content(format!("Exported **{}** messages.", rows))
+                .new_attachment(CreateAttachment::bytes(data, file_name(&filter, format))),
+            Ok((ExportOutput::File(path), rows)) => EditInteractionResponse::new().content(format!(
+                "Exported **{}** messages. The export was too large to attach, so it was saved on the host as `{}`.",
+                rows,
+                path.display()
+            )),
+            Err(e) => {
+                eprintln!("Failed to export messages: {}", e);
+                EditInteractionResponse::new().content("Something went wrong while exporting messages.")
+            }
+        };
+
+        command.edit_response(&ctx.http, builder).await?;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("export")
+            .description("Export this server's stored messages.")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "format", "The file format")
+                    .add_string_choice("JSON Lines", "jsonl")
+                    .add_string_choice("CSV", "csv"),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "Only export messages from this channel",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::User,
+                "user",
+                "Only export messages from this user",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "since",
+                "Only export messages sent on or after this date (YYYY-MM-DD)",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "until",
+                "Only export messages sent on or before this date (YYYY-MM-DD)",
+            ))
+    }
+}
+
+async fn invalid_date(
+    ctx: &Context,
+    command: &CommandInteraction,
+    date: &str,
+) -> Result<(), Error> {
+    let builder = EditInteractionResponse::new().content(format!(
+        "`{}` is not a valid date, please use the `YYYY-MM-DD` format.",
+        date
+    ));
+    command.edit_response(&ctx.http, builder).await?;
+    Ok(())
+}
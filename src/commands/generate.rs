@@ -1,51 +1,437 @@
 use serenity::all::{
-    CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption,
-    EditInteractionResponse, ResolvedValue,
+    ButtonStyle, Cache, ChannelId, CommandInteraction, CommandOptionType, ComponentInteraction,
+    CreateActionRow, CreateAutocompleteResponse, CreateButton, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    EditInteractionResponse, GuildId, ResolvedValue, UserId,
 };
+use serenity::async_trait;
 use serenity::prelude::*;
 use serenity::Error;
 use std::sync::Arc;
 
+use crate::commands::Command;
 use crate::database::Database;
-use crate::utils::helpers::generate_markov_message;
+use crate::settings::GuildSettings;
+use crate::state::AppState;
+use crate::utils::helpers::{
+    build_vocabulary, generate_markov_message, log_generation, GeneratedMessage,
+};
+use crate::utils::visibility;
 
-pub async fn execute(ctx: &Context, command: &CommandInteraction, database: Arc<Database>) -> Result<(), Error> {
-    command.defer(&ctx.http).await?;
+/// Discord's limit on the length of a message.
+const MAX_MESSAGE_LENGTH: usize = 2000;
 
-    let guild_id = match command.guild_id {
-        Some(s) => s,
-        _ => return Ok(()),
-    };
+/// Discord's limit on the amount of autocomplete choices.
+const MAX_CHOICES: usize = 25;
+
+pub struct Generate;
+
+#[async_trait]
+impl Command for Generate {
+    fn name(&self) -> &'static str {
+        "generate"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        let word = options
+            .iter()
+            .find(|opt| opt.name == "word")
+            .and_then(|opt| {
+                if let ResolvedValue::String(s) = &opt.value {
+                    Some(*s)
+                } else {
+                    None
+                }
+            });
+
+        let (generated, settings) = match generate(
+            &ctx.cache,
+            state.database.clone(),
+            guild_id,
+            command.channel_id,
+            word,
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(reason) => {
+                command
+                    .edit_response(&ctx.http, EditInteractionResponse::new().content(reason))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let posted = command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new()
+                    .content(&generated.content)
+                    .components(vec![buttons(command.user.id, word)]),
+            )
+            .await?;
+
+        log_generation(
+            state.database.as_ref(),
+            guild_id,
+            &posted,
+            command.channel_id,
+            &generated,
+            &settings,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Suggests seed words the channel's chain can continue, as they are typed.
+    async fn autocomplete(
+        &self,
+        ctx: &Context,
+        interaction: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        let guild_id = match interaction.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        let typed = match interaction.data.autocomplete() {
+            Some(option) if option.name == "word" => option.value,
+            _ => return Ok(()),
+        };
+
+        let channel_id = interaction.channel_id;
+        let generate_from = match state
+            .lookups
+            .channel_flags(
+                state.database.as_ref(),
+                guild_id.get(),
+                channel_id.get(),
+                visibility::thread_parent(&ctx.cache, guild_id, channel_id),
+            )
+            .await
+        {
+            Ok(flags) => flags.generate_from,
+            Err(e) => {
+                eprintln!("Failed to get channel flags: {}", e);
+                false
+            }
+        };
+        let settings = state
+            .lookups
+            .settings(state.database.as_ref(), guild_id.get())
+            .await;
+
+        // Channels that can't be generated from have nothing to suggest
+        let vocabulary = if !generate_from || settings.is_command_disabled(self.name()) {
+            Arc::default()
+        } else if let Some(vocabulary) = state.vocabularies.get(channel_id) {
+            vocabulary
+        } else {
+            // Building takes longer than Discord waits for suggestions, so it
+            // happens in the background and later keystrokes get them
+            if state.vocabularies.start_building(channel_id) {
+                let vocabularies = state.vocabularies.clone();
+                let database = state.database.clone();
+                tokio::spawn(async move {
+                    let vocabulary = build_vocabulary(guild_id, channel_id, database, &settings)
+                        .await
+                        .unwrap_or_default();
+                    vocabularies.insert(channel_id, vocabulary);
+                });
+            }
+            Arc::default()
+        };
+
+        let mut response = CreateAutocompleteResponse::new();
+        for suggestion in vocabulary.suggest(typed, MAX_CHOICES) {
+            response = response.add_string_choice(suggestion, suggestion);
+        }
+
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+            .await
+    }
+
+    /// Handles the buttons below a generated message. Their custom ids carry the
+    /// options it was generated with, so they keep working after a restart.
+    async fn handle_component(
+        &self,
+        ctx: &Context,
+        interaction: &ComponentInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        let guild_id = match interaction.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        // generate:<action>:<invoker>:<word>, the word may contain colons itself
+        let mut parts = interaction.data.custom_id.splitn(4, ':').skip(1);
+        let (action, invoker, word) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(action), Some(invoker), Some(word)) => match invoker.parse::<u64>() {
+                Ok(invoker) => (
+                    action,
+                    UserId::new(invoker),
+                    Some(word).filter(|w| !w.is_empty()),
+                ),
+                Err(_) => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
 
-    let options = &command.data.options();
+        if action == "delete" {
+            let is_moderator = interaction
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.manage_messages());
 
-    let word = options
-        .iter()
-        .find(|opt| opt.name == "word")
-        .and_then(|opt| {
-            if let ResolvedValue::String(s) = &opt.value {
-                Some(*s)
-            } else {
-                None
+            if interaction.user.id != invoker && !is_moderator {
+                interaction
+                    .create_response(
+                        &ctx.http,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content("Only the member who generated this message or a moderator can delete it.")
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
             }
-        });
 
-    let builder = match generate_markov_message(guild_id, command.channel_id, word, database).await {
-        Some(markov_message) => EditInteractionResponse::new().content(markov_message),
-        None => EditInteractionResponse::new()
-            .content("Please wait until this channel has over 500 messages."),
+            interaction
+                .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+                .await?;
+            interaction.delete_response(&ctx.http).await?;
+
+            if let Err(e) = state
+                .database
+                .delete_generation(interaction.message.id.get())
+                .await
+            {
+                eprintln!("Failed to delete generation: {}", e);
+            }
+            return Ok(());
+        }
+
+        // Generating again is held back like the command itself
+        if let Err(reason) = super::authorize(
+            self,
+            Some(guild_id),
+            interaction.member.as_ref(),
+            interaction.channel_id,
+            state,
+        )
+        .await
+        {
+            interaction
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(reason)
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        let result = match action {
+            // Start over with the same options
+            "regenerate" => {
+                generate(
+                    &ctx.cache,
+                    state.database.clone(),
+                    guild_id,
+                    interaction.channel_id,
+                    word,
+                )
+                .await
+            }
+            // Pick up from the last word of the message
+            "continue" => {
+                let previous = &interaction.message.content;
+                let last_word = match previous.split_whitespace().last() {
+                    Some(last_word) => last_word,
+                    None => return Ok(()),
+                };
+
+                match generate(
+                    &ctx.cache,
+                    state.database.clone(),
+                    guild_id,
+                    interaction.channel_id,
+                    Some(last_word),
+                )
+                .await
+                {
+                    Ok((generated, settings)) => {
+                        let extension = generated
+                            .content
+                            .strip_prefix(last_word)
+                            .unwrap_or_default()
+                            .trim_start();
+                        let content = format!("{} {}", previous, extension);
+
+                        if extension.is_empty() {
+                            Err("This message can't be continued any further.".to_string())
+                        } else if content.chars().count() > MAX_MESSAGE_LENGTH {
+                            Err("This message is too long to be continued.".to_string())
+                        } else {
+                            Ok((
+                                GeneratedMessage {
+                                    content,
+                                    seed: word.map(String::from),
+                                    max_words: generated.max_words,
+                                },
+                                settings,
+                            ))
+                        }
+                    }
+                    Err(reason) => Err(reason),
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        let (generated, settings) = match result {
+            Ok(result) => result,
+            Err(reason) => {
+                interaction
+                    .create_followup(
+                        &ctx.http,
+                        CreateInteractionResponseFollowup::new()
+                            .content(reason)
+                            .ephemeral(true),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let posted = interaction
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new().content(&generated.content),
+            )
+            .await?;
+
+        // Reactions were given to what the message said before
+        if let Err(e) = state
+            .database
+            .remove_generation_reactions(posted.id.get(), None, None)
+            .await
+        {
+            eprintln!("Failed to reset generation reactions: {}", e);
+        }
+
+        log_generation(
+            state.database.as_ref(),
+            guild_id,
+            &posted,
+            interaction.channel_id,
+            &generated,
+            &settings,
+        )
+        .await;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("generate")
+            .description("Generates a markov message.")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "word",
+                    "What the sentence will start with",
+                )
+                // Custom ids are limited to 100 characters, and the buttons carry the word
+                .max_length(50)
+                .set_autocomplete(true),
+            )
+    }
+}
+
+/// Generates a message from a channel, or returns why it can't be.
+async fn generate(
+    cache: &Cache,
+    database: Arc<Database>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    word: Option<&str>,
+) -> Result<(GeneratedMessage, GuildSettings), String> {
+    let generate_from = match database
+        .get_channel_flags(
+            guild_id.get(),
+            channel_id.get(),
+            visibility::thread_parent(cache, guild_id, channel_id),
+        )
+        .await
+    {
+        Ok(flags) => flags.generate_from,
+        Err(e) => {
+            eprintln!("Failed to get channel flags: {}", e);
+            false
+        }
     };
 
-    command.edit_response(&ctx.http, builder).await?;
-    Ok(())
+    if !generate_from {
+        return Err("Messages from this channel aren't used to generate text.".to_string());
+    }
+
+    let settings = GuildSettings::load(database.as_ref(), guild_id.get()).await;
+
+    match generate_markov_message(guild_id, channel_id, word, database.clone(), &settings).await {
+        Some(generated) => Ok((generated, settings)),
+        None => Err(format!(
+            "Please wait until this channel has over {} messages.",
+            settings.min_messages
+        )),
+    }
 }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("generate")
-        .description("Generates a markov message.")
-        .add_option(CreateCommandOption::new(
-            CommandOptionType::String,
-            "word",
-            "What the sentence will start with",
-        ))
+/// The buttons below a generated message.
+fn buttons(invoker: UserId, word: Option<&str>) -> CreateActionRow {
+    let custom_id = |action: &str| {
+        format!(
+            "generate:{}:{}:{}",
+            action,
+            invoker,
+            word.unwrap_or_default()
+        )
+    };
+
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(custom_id("regenerate"))
+            .style(ButtonStyle::Primary)
+            .label("Regenerate"),
+        CreateButton::new(custom_id("continue"))
+            .style(ButtonStyle::Secondary)
+            .label("Continue"),
+        CreateButton::new(custom_id("delete"))
+            .style(ButtonStyle::Danger)
+            .label("Delete"),
+    ])
 }
@@ -0,0 +1,155 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, CreateEmbed,
+    EditInteractionResponse, Permissions, ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::hall_of_fame;
+use crate::state::AppState;
+
+/// How many generations are listed unless asked otherwise.
+const DEFAULT_COUNT: i64 = 10;
+
+pub struct Generations;
+
+#[async_trait]
+impl Command for Generations {
+    fn name(&self) -> &'static str {
+        "generations"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s.get(),
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        let (subcommand, options) = match options.first() {
+            Some(ResolvedOption {
+                name,
+                value: ResolvedValue::SubCommand(options),
+                ..
+            }) => (*name, options),
+            _ => return Ok(()),
+        };
+
+        let best = match subcommand {
+            "best" => true,
+            "worst" => false,
+            _ => return Ok(()),
+        };
+
+        let count = options
+            .iter()
+            .find(|opt| opt.name == "count")
+            .and_then(|opt| {
+                if let ResolvedValue::Integer(count) = opt.value {
+                    Some(count)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(DEFAULT_COUNT);
+
+        let generations = match state
+            .database
+            .get_ranked_generations(guild_id, best, count as u64)
+            .await
+        {
+            Ok(generations) => generations,
+            Err(e) => {
+                eprintln!("Failed to get ranked generations: {}", e);
+                command
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .content("Something went wrong, please try again later."),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let description = if generations.is_empty() {
+            format!(
+                "No generated message has been {} yet. React with 👍 or 👎 to rate them.",
+                if best { "liked" } else { "disliked" }
+            )
+        } else {
+            generations
+                .iter()
+                .map(|generation| {
+                    format!(
+                        "**{:+}** {} ([jump]({}))",
+                        generation.score,
+                        generation.content,
+                        hall_of_fame::jump_link(generation)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new().embed(
+                    CreateEmbed::new()
+                        .title(if best {
+                            "Best Generated Messages"
+                        } else {
+                            "Worst Generated Messages"
+                        })
+                        .description(description)
+                        .color(0x5865F2),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("generations")
+            .description("See which generated messages members liked and disliked the most.")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "best",
+                    "Show the highest rated generated messages",
+                )
+                .add_sub_option(count_option()),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "worst",
+                    "Show the lowest rated generated messages",
+                )
+                .add_sub_option(count_option()),
+            )
+    }
+}
+
+fn count_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::Integer,
+        "count",
+        "How many messages to show",
+    )
+    .min_int_value(1)
+    .max_int_value(25)
+}
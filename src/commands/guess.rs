@@ -4,136 +4,164 @@ use std::time::Duration;
 use futures::StreamExt;
 use serenity::all::{
     ButtonStyle, CommandInteraction, CreateButton, CreateCommand, CreateEmbed,
-    CreateInteractionResponse, CreateMessage, EditInteractionResponse, Message, User, UserId,
+    CreateInteractionResponse, CreateMessage, EditInteractionResponse, GuildId, Message, User,
+    UserId,
 };
+use serenity::async_trait;
 use serenity::prelude::*;
 use serenity::Error;
 
+use crate::commands::Command;
 use crate::database::Database;
+use crate::settings::GuildSettings;
+use crate::state::AppState;
 use crate::utils::string_cmp::{gestalt_pattern_matching, levenshtein_similarity};
+use crate::utils::visibility;
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("guess").description("Guess who a random message belongs to.")
-}
+pub struct Guess;
 
-pub async fn execute(
-    ctx: &Context,
-    command: &CommandInteraction,
-    database: Arc<Database>,
-) -> Result<(), Error> {
-    command.defer(&ctx.http).await?;
+#[async_trait]
+impl Command for Guess {
+    fn name(&self) -> &'static str {
+        "guess"
+    }
 
-    let game_stop_seconds = 180;
-    let embed = CreateEmbed::new()
-        .title("Message Guesser")
-        .description(format!(
-            "**How to play:**\n\
-            • Bot picks a random message from this server\n\
-            • Guess who wrote it using their nickname, username, or user ID\n\
-            • Game automatically ends after {} minutes of inactivity\n\n\
-            Ready to test your memory?",
-            game_stop_seconds / 60
-        ))
-        .color(0x5865F2);
-
-    let message = command
-        .edit_response(
-            &ctx.http,
-            EditInteractionResponse::new()
-                .embed(embed)
-                .button(
-                    CreateButton::new("start")
-                        .style(ButtonStyle::Success)
-                        .label("Start"),
-                )
-                .button(
-                    CreateButton::new("cancel")
-                        .style(ButtonStyle::Danger)
-                        .label("Cancel"),
-                ),
-        )
-        .await?;
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer(&ctx.http).await?;
 
-    let interaction = match message
-        .await_component_interaction(&ctx.shard)
-        .timeout(Duration::from_secs(60))
-        .await
-    {
-        Some(x) => x,
-        None => {
-            let embed = CreateEmbed::new()
-                .title("Message Guesser")
-                .description("**Game Cancelled**\n\nNo response received within 60 seconds.")
-                .color(0xED4245);
-
-            command
-                .edit_response(
-                    &ctx.http,
-                    EditInteractionResponse::new()
-                        .embed(embed)
-                        .button(
-                            CreateButton::new("start")
-                                .style(ButtonStyle::Success)
-                                .label("Start")
-                                .disabled(true),
-                        )
-                        .button(
-                            CreateButton::new("cancel")
-                                .style(ButtonStyle::Danger)
-                                .label("Cancel")
-                                .disabled(true),
-                        ),
-                )
-                .await?;
+        let guild_id = match command.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
 
-            return Ok(());
-        }
-    };
+        let settings = GuildSettings::load(state.database.as_ref(), guild_id.get()).await;
 
-    interaction
-        .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
-        .await?;
+        let embed = CreateEmbed::new()
+            .title("Message Guesser")
+            .description(format!(
+                "**How to play:**\n\
+                • Bot picks a random message from this server\n\
+                • Guess who wrote it using their nickname, username, or user ID\n\
+                • Game automatically ends after {} of inactivity\n\n\
+                Ready to test your memory?",
+                match settings.game_timeout_seconds {
+                    seconds if seconds % 60 == 0 => format!("{} minutes", seconds / 60),
+                    seconds => format!("{} seconds", seconds),
+                }
+            ))
+            .color(0x5865F2);
+
+        let message = command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new()
+                    .embed(embed)
+                    .button(
+                        CreateButton::new("start")
+                            .style(ButtonStyle::Success)
+                            .label("Start"),
+                    )
+                    .button(
+                        CreateButton::new("cancel")
+                            .style(ButtonStyle::Danger)
+                            .label("Cancel"),
+                    ),
+            )
+            .await?;
 
-    match interaction.data.custom_id.as_str() {
-        "start" => {
-            start_game(ctx, command, database).await?;
-        }
-        "cancel" => {
-            let embed = CreateEmbed::new()
-                .title("Message Guesser")
-                .description("**Game Cancelled**\n\nThe game has been cancelled by user request.")
-                .color(0xED4245);
-
-            command
-                .edit_response(
-                    &ctx.http,
-                    EditInteractionResponse::new()
-                        .embed(embed)
-                        .button(
-                            CreateButton::new("start")
-                                .style(ButtonStyle::Success)
-                                .label("Start")
-                                .disabled(true),
-                        )
-                        .button(
-                            CreateButton::new("cancel")
-                                .style(ButtonStyle::Danger)
-                                .label("Cancel")
-                                .disabled(true),
-                        ),
-                )
-                .await?;
-        }
-        _ => {}
-    };
+        let interaction = match message
+            .await_component_interaction(&ctx.shard)
+            .timeout(Duration::from_secs(60))
+            .await
+        {
+            Some(x) => x,
+            None => {
+                let embed = CreateEmbed::new()
+                    .title("Message Guesser")
+                    .description("**Game Cancelled**\n\nNo response received within 60 seconds.")
+                    .color(0xED4245);
+
+                command
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .embed(embed)
+                            .button(
+                                CreateButton::new("start")
+                                    .style(ButtonStyle::Success)
+                                    .label("Start")
+                                    .disabled(true),
+                            )
+                            .button(
+                                CreateButton::new("cancel")
+                                    .style(ButtonStyle::Danger)
+                                    .label("Cancel")
+                                    .disabled(true),
+                            ),
+                    )
+                    .await?;
 
-    Ok(())
+                return Ok(());
+            }
+        };
+
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        match interaction.data.custom_id.as_str() {
+            "start" => {
+                start_game(ctx, command, state.database.clone(), settings).await?;
+            }
+            "cancel" => {
+                let embed = CreateEmbed::new()
+                    .title("Message Guesser")
+                    .description(
+                        "**Game Cancelled**\n\nThe game has been cancelled by user request.",
+                    )
+                    .color(0xED4245);
+
+                command
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .embed(embed)
+                            .button(
+                                CreateButton::new("start")
+                                    .style(ButtonStyle::Success)
+                                    .label("Start")
+                                    .disabled(true),
+                            )
+                            .button(
+                                CreateButton::new("cancel")
+                                    .style(ButtonStyle::Danger)
+                                    .label("Cancel")
+                                    .disabled(true),
+                            ),
+                    )
+                    .await?;
+            }
+            _ => {}
+        };
+
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("guess").description("Guess who a random message belongs to.")
+    }
 }
 
 async fn start_game(
     ctx: &Context,
     command: &CommandInteraction,
     database: Arc<Database>,
+    settings: GuildSettings,
 ) -> Result<(), Error> {
     let embed = CreateEmbed::new()
         .title("Message Guesser")
@@ -159,7 +187,7 @@ async fn start_game(
         )
         .await?;
 
-    let mut game = Game::new(ctx, command, database);
+    let mut game = Game::new(ctx, command, database, settings);
     game.start_game().await?;
 
     Ok(())
@@ -169,15 +197,22 @@ struct Game<'a> {
     pub ctx: &'a Context,
     pub command: &'a CommandInteraction,
     pub database: Arc<Database>,
+    pub settings: GuildSettings,
     pub game_ended: bool,
 }
 
 impl<'a> Game<'a> {
-    pub fn new(ctx: &'a Context, command: &'a CommandInteraction, database: Arc<Database>) -> Self {
+    pub fn new(
+        ctx: &'a Context,
+        command: &'a CommandInteraction,
+        database: Arc<Database>,
+        settings: GuildSettings,
+    ) -> Self {
         Self {
             ctx,
             command,
             database,
+            settings,
             game_ended: false,
         }
     }
@@ -242,7 +277,12 @@ impl<'a> Game<'a> {
             let mut interaction_stream = message
                 .await_component_interaction(&self.ctx.shard)
                 .stream();
-            let mut message_stream = self.command.channel_id.await_reply(&self.ctx).stream();
+            let mut message_stream = self
+                .command
+                .channel_id
+                .await_reply(&self.ctx)
+                .timeout(Duration::from_secs(self.settings.game_timeout_seconds))
+                .stream();
 
             tokio::select! {
                 interaction = interaction_stream.next() => {
@@ -443,13 +483,23 @@ impl<'a> Game<'a> {
         guild_id: &u64,
         min_letters_amount: &u64,
     ) -> Option<(String, u64)> {
-        let prefix_list: Vec<&str> = vec![
-            "$", "&", "!", ".", "m.", ">", "<", "[", "]", "@", "#", "^", "*", ",", "https", "http",
-        ];
+        // Only pick messages the player could have read themselves
+        let readable = match &self.command.member {
+            Some(member) => {
+                visibility::readable_by_member(&self.ctx.cache, GuildId::new(*guild_id), member)
+            }
+            None => Vec::new(),
+        };
 
         match self
             .database
-            .get_random_message(*guild_id, *min_letters_amount, &prefix_list)
+            .get_random_message(
+                *guild_id,
+                *min_letters_amount,
+                &self.settings.prefixes(),
+                self.settings.store_bot_messages,
+                Some(&readable),
+            )
             .await
         {
             Ok(result) => result,
@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    EditInteractionResponse, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::database::Generation;
+use crate::hall_of_fame;
+use crate::state::AppState;
+
+const RESULTS_PER_PAGE: u64 = 5;
+
+pub struct HallOfFame;
+
+#[async_trait]
+impl Command for HallOfFame {
+    fn name(&self) -> &'static str {
+        "halloffame"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        // Pages are counted from 1 in the option, from 0 here
+        let mut page = options
+            .iter()
+            .find(|opt| opt.name == "page")
+            .and_then(|opt| {
+                if let ResolvedValue::Integer(page) = opt.value {
+                    Some(page.max(1) as u64 - 1)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(0);
+
+        loop {
+            let (total, generations) = match state
+                .database
+                .get_featured_generations(guild_id.get(), RESULTS_PER_PAGE, page * RESULTS_PER_PAGE)
+                .await
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("Failed to get featured generations: {}", e);
+                    command
+                        .edit_response(
+                            &ctx.http,
+                            EditInteractionResponse::new()
+                                .content("Something went wrong, please try again later."),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            if total == 0 {
+                command
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new().content(
+                            "The hall of fame is empty, no generated message was liked enough yet.",
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+
+            let pages = total.div_ceil(RESULTS_PER_PAGE);
+
+            // A page past the end shows the last one
+            if page >= pages {
+                page = pages - 1;
+                continue;
+            }
+
+            let embed = create_embed(&generations, page, pages, total);
+
+            let message = command
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .embed(embed.clone())
+                        .button(
+                            CreateButton::new("previous")
+                                .style(ButtonStyle::Secondary)
+                                .label("Previous")
+                                .disabled(page == 0),
+                        )
+                        .button(
+                            CreateButton::new("next")
+                                .style(ButtonStyle::Secondary)
+                                .label("Next")
+                                .disabled(page + 1 >= pages),
+                        ),
+                )
+                .await?;
+
+            let interaction = match message
+                .await_component_interaction(&ctx.shard)
+                .author_id(command.user.id)
+                .timeout(Duration::from_secs(120))
+                .await
+            {
+                Some(x) => x,
+                None => {
+                    // Stop paginating, but keep the last page visible
+                    command
+                        .edit_response(
+                            &ctx.http,
+                            EditInteractionResponse::new()
+                                .embed(embed)
+                                .components(vec![]),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            interaction
+                .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+                .await?;
+
+            match interaction.data.custom_id.as_str() {
+                "previous" => page = page.saturating_sub(1),
+                "next" => page = (page + 1).min(pages - 1),
+                _ => {}
+            }
+        }
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("halloffame")
+            .description("Browse the best generated messages of this server.")
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "page",
+                    "The page to start on",
+                )
+                .min_int_value(1),
+            )
+    }
+}
+
+fn create_embed(generations: &[Generation], page: u64, pages: u64, total: u64) -> CreateEmbed {
+    let mut description = String::new();
+
+    for generation in generations {
+        description.push_str(&format!(
+            "**{:+}** in <#{}> — [Jump]({})\n> {}\n\n",
+            generation.score,
+            generation.channel_id,
+            hall_of_fame::jump_link(generation),
+            generation.content.replace('\n', "\n> ")
+        ));
+    }
+
+    CreateEmbed::new()
+        .title("Hall of Fame")
+        .description(description.trim_end())
+        .color(0xFEE75C)
+        .footer(CreateEmbedFooter::new(format!(
+            "Page {} of {} • {} messages",
+            page + 1,
+            pages,
+            total
+        )))
+}
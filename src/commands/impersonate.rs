@@ -0,0 +1,102 @@
+use serenity::all::{
+    CommandInteraction, CommandType, CreateCommand, CreateEmbed, CreateEmbedAuthor,
+    EditInteractionResponse, ResolvedTarget,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::settings::GuildSettings;
+use crate::state::AppState;
+use crate::utils::helpers::{generate_impersonation, log_generation};
+use crate::utils::visibility;
+
+pub struct Impersonate;
+
+#[async_trait]
+impl Command for Impersonate {
+    fn name(&self) -> &'static str {
+        "Impersonate"
+    }
+
+    fn kind(&self) -> CommandType {
+        CommandType::User
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        let (user, member) = match command.data.target() {
+            Some(ResolvedTarget::User(user, member)) => (user, member),
+            _ => return Ok(()),
+        };
+
+        let name = member
+            .and_then(|member| member.nick.clone())
+            .unwrap_or_else(|| user.display_name().to_string());
+
+        // Only draw on messages the invoker could have read themselves
+        let readable = match &command.member {
+            Some(member) => visibility::readable_by_member(&ctx.cache, guild_id, member),
+            None => Vec::new(),
+        };
+
+        let settings = GuildSettings::load(state.database.as_ref(), guild_id.get()).await;
+
+        let generated = generate_impersonation(
+            guild_id,
+            user.id,
+            &readable,
+            state.database.clone(),
+            &settings,
+        )
+        .await;
+
+        let builder = match &generated {
+            Some(generated) => EditInteractionResponse::new().embed(
+                CreateEmbed::new()
+                    .author(CreateEmbedAuthor::new(&name).icon_url(user.face()))
+                    .description(&generated.content)
+                    .color(0x5865F2),
+            ),
+            None => EditInteractionResponse::new().content(format!(
+                "{} hasn't sent enough messages here to be impersonated.",
+                name
+            )),
+        };
+
+        let posted = command.edit_response(&ctx.http, builder).await?;
+
+        // Drawn from every channel the invoker can read, so it counts as coming
+        // from where it was posted, which the hall of fame checks before reposting
+        if let Some(generated) = generated {
+            log_generation(
+                state.database.as_ref(),
+                guild_id,
+                &posted,
+                command.channel_id,
+                &generated,
+                &settings,
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("Impersonate")
+            .kind(CommandType::User)
+            .dm_permission(false)
+    }
+}
@@ -0,0 +1,113 @@
+use serenity::all::{
+    CommandInteraction, CommandType, CreateCommand, EditInteractionResponse, ResolvedTarget,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::settings::GuildSettings;
+use crate::state::AppState;
+use crate::utils::helpers::{generate_markov_reply, log_generation};
+use crate::utils::visibility;
+
+pub struct MarkovFromMessage;
+
+#[async_trait]
+impl Command for MarkovFromMessage {
+    fn name(&self) -> &'static str {
+        "Markov from this message"
+    }
+
+    fn kind(&self) -> CommandType {
+        CommandType::Message
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        let message = match command.data.target() {
+            Some(ResolvedTarget::Message(message)) => message,
+            _ => return Ok(()),
+        };
+
+        let generate_from = match state
+            .database
+            .get_channel_flags(
+                guild_id.get(),
+                command.channel_id.get(),
+                visibility::thread_parent(&ctx.cache, guild_id, command.channel_id),
+            )
+            .await
+        {
+            Ok(flags) => flags.generate_from,
+            Err(e) => {
+                eprintln!("Failed to get channel flags: {}", e);
+                false
+            }
+        };
+
+        if !generate_from {
+            command
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .content("Messages from this channel aren't used to generate text."),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let settings = GuildSettings::load(state.database.as_ref(), guild_id.get()).await;
+
+        // Seeded from the message's keywords, like a reply to it would be
+        let generated = generate_markov_reply(
+            guild_id,
+            command.channel_id,
+            &[&message.content],
+            &[],
+            state.database.clone(),
+            &settings,
+        )
+        .await;
+
+        let builder = match &generated {
+            Some(generated) => EditInteractionResponse::new().content(&generated.content),
+            None => EditInteractionResponse::new().content(format!(
+                "Please wait until this channel has over {} messages.",
+                settings.min_messages
+            )),
+        };
+
+        let posted = command.edit_response(&ctx.http, builder).await?;
+
+        if let Some(generated) = generated {
+            log_generation(
+                state.database.as_ref(),
+                guild_id,
+                &posted,
+                command.channel_id,
+                &generated,
+                &settings,
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("Markov from this message")
+            .kind(CommandType::Message)
+            .dm_permission(false)
+    }
+}
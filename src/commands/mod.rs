@@ -1,54 +1,256 @@
+pub mod autopost;
+pub mod backup;
+pub mod channels;
+pub mod config;
+pub mod export;
 pub mod generate;
+pub mod generations;
 pub mod guess;
+pub mod halloffame;
+pub mod impersonate;
 pub mod leaderboard;
+pub mod markov_from_message;
+pub mod permissions;
 pub mod ping;
+pub mod privacy;
+pub mod search;
 
-use serenity::all::{CommandInteraction, CreateCommand};
-use serenity::futures::future::BoxFuture;
+use serenity::all::{
+    ChannelId, CommandInteraction, CommandType, ComponentInteraction, CreateCommand, GuildId, Http,
+    Member, Permissions,
+};
+use serenity::async_trait;
 use serenity::prelude::*;
 use serenity::Error;
-use std::sync::Arc;
+use std::env;
+use tokio::time::Duration;
 
-use crate::database::Database;
+use crate::cooldowns::Cooldown;
+use crate::state::AppState;
 
-type CommandFn = for<'a> fn(
-    &'a Context,            // Command context, `ctx`
-    &'a CommandInteraction, // Command interaction, `command`
-    Arc<Database>,          // Database connection
-) -> BoxFuture<'a, Result<(), Error>>;
+/// A slash command or context menu entry. Everything about a command lives on
+/// its type, so adding one means implementing this and listing it in `COMMANDS`.
+#[async_trait]
+pub trait Command: Send + Sync {
+    /// The name the command is registered and dispatched by.
+    fn name(&self) -> &'static str;
 
-#[derive(Debug)]
-pub struct Command {
-    pub name: String,
-    pub exec: CommandFn,
+    /// Slash commands are `CommandType::ChatInput`, context menu entries
+    /// `User` or `Message`.
+    fn kind(&self) -> CommandType {
+        CommandType::ChatInput
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error>;
+
+    /// Suggests values for the options registered with autocomplete.
+    async fn autocomplete(
+        &self,
+        _ctx: &Context,
+        _interaction: &CommandInteraction,
+        _state: &AppState,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Handles the message components whose custom id starts with the
+    /// command's name and a `:`.
+    ///
+    /// Components awaited by a running command, such as page buttons, don't
+    /// need this. Components that act as the command should check `authorize`
+    /// first, it isn't done for them.
+    async fn handle_component(
+        &self,
+        _ctx: &Context,
+        _interaction: &ComponentInteraction,
+        _state: &AppState,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand;
+}
+
+/// Every command, in the order they are registered.
+pub static COMMANDS: &[&dyn Command] = &[
+    &ping::Ping,
+    &generate::Generate,
+    &leaderboard::Leaderboard,
+    &guess::Guess,
+    &export::Export,
+    &privacy::Privacy,
+    &search::Search,
+    &config::Config,
+    &channels::Channels,
+    &backup::Backup,
+    &autopost::Autopost,
+    &generations::Generations,
+    &halloffame::HallOfFame,
+    &markov_from_message::MarkovFromMessage,
+    &impersonate::Impersonate,
+    &permissions::CommandPermissions,
+];
+
+/// Commands a server can neither disable nor hold back with a rule: `/config`
+/// so nothing can lock it out of its settings, and `/privacy` so every member
+/// can always opt out or have their data forgotten.
+pub const PROTECTED_COMMANDS: &[&str] = &["config", "privacy"];
+
+/// Finds the command an interaction is for.
+pub fn find(name: &str, kind: CommandType) -> Option<&'static dyn Command> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name() == name && command.kind() == kind)
+        .copied()
 }
 
-pub fn commands_vecs() -> Vec<Command> {
-    vec![
-        Command {
-            name: "ping".into(),
-            exec: |ctx, command, _db| Box::pin(ping::execute(ctx, command)),
-        },
-        Command {
-            name: "guess".into(),
-            exec: |ctx, command, db| Box::pin(guess::execute(ctx, command, db)),
-        },
-        Command {
-            name: "generate".into(),
-            exec: |ctx, command, db| Box::pin(generate::execute(ctx, command, db)),
-        },
-        Command {
-            name: "leaderboard".into(),
-            exec: |ctx, command, db| Box::pin(leaderboard::execute(ctx, command, db)),
-        },
-    ]
+/// Finds the command that handles a component, by the prefix of its custom id.
+pub fn find_component(custom_id: &str) -> Option<&'static dyn Command> {
+    let prefix = custom_id.split(':').next()?;
+    COMMANDS
+        .iter()
+        .find(|command| command.name() == prefix)
+        .copied()
 }
 
 pub fn register_vecs() -> Vec<CreateCommand> {
-    vec![
-        ping::register(),
-        generate::register(),
-        leaderboard::register(),
-        guess::register(),
-    ]
+    COMMANDS.iter().map(|command| command.register()).collect()
+}
+
+/// Checks whether the member may run a command, returning why not otherwise,
+/// and starts its cooldowns if so. Members with Manage Server are never held
+/// back by a command's rule, so they can't lock themselves out.
+///
+/// Takes the parts of the interaction it needs, so components that act as
+/// their command, such as the buttons below a generated message, are checked
+/// the same way.
+pub async fn authorize(
+    command: &dyn Command,
+    guild_id: Option<GuildId>,
+    member: Option<&Member>,
+    channel_id: ChannelId,
+    state: &AppState,
+) -> Result<(), String> {
+    let (guild_id, member) = match (guild_id, member) {
+        (Some(guild_id), Some(member)) => (guild_id, member),
+        _ => return Ok(()),
+    };
+
+    if PROTECTED_COMMANDS.contains(&command.name()) {
+        return Ok(());
+    }
+
+    let settings = state
+        .lookups
+        .settings(state.database.as_ref(), guild_id.get())
+        .await;
+    if settings.is_command_disabled(command.name()) {
+        return Err("This command is disabled on this server.".into());
+    }
+
+    let rule = match state
+        .database
+        .get_command_rule(guild_id.get(), command.name())
+        .await
+    {
+        Ok(Some(rule)) => rule,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            eprintln!("Failed to get command rule: {}", e);
+            return Ok(());
+        }
+    };
+
+    let permissions = member.permissions.unwrap_or_else(Permissions::empty);
+    if permissions.manage_guild() {
+        return Ok(());
+    }
+
+    if !rule.role_ids.is_empty()
+        && !member
+            .roles
+            .iter()
+            .any(|role_id| rule.role_ids.contains(&role_id.get()))
+    {
+        let roles: Vec<String> = rule
+            .role_ids
+            .iter()
+            .map(|role_id| format!("<@&{}>", role_id))
+            .collect();
+        return Err(format!(
+            "You need one of these roles to use this command: {}",
+            roles.join(", ")
+        ));
+    }
+
+    let required = Permissions::from_bits_truncate(rule.permissions);
+    if !permissions.contains(required) {
+        return Err(format!(
+            "You need the {} permission to use this command.",
+            required.get_permission_names().join(", ")
+        ));
+    }
+
+    let cooldown = state.cooldowns.try_use(
+        command.name(),
+        guild_id,
+        member.user.id,
+        channel_id,
+        Duration::from_secs(rule.user_cooldown_seconds),
+        Duration::from_secs(rule.channel_cooldown_seconds),
+    );
+
+    // Round up, so the relative time never says it's ready too early
+    let ready_at = |left: Duration| chrono::Utc::now().timestamp() + left.as_secs() as i64 + 1;
+    match cooldown {
+        Ok(()) => Ok(()),
+        Err(Cooldown::Member(left)) => Err(format!(
+            "You can use this command again <t:{}:R>.",
+            ready_at(left)
+        )),
+        Err(Cooldown::Channel(left)) => Err(format!(
+            "This command was just used in this channel, it can be used again <t:{}:R>.",
+            ready_at(left)
+        )),
+    }
+}
+
+/// The guilds commands are registered in instead of globally, read from the
+/// comma or space separated ids in `COMMAND_GUILDS`. Guild commands update
+/// instantly, which suits development servers.
+pub fn command_guilds() -> Vec<GuildId> {
+    env::var("COMMAND_GUILDS")
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|id| id.parse::<u64>().ok())
+        .filter(|&id| id != 0)
+        .map(GuildId::new)
+        .collect()
+}
+
+/// Registers the commands a guild hasn't disabled in it, if commands are
+/// registered per guild and it is one of the state's `command_guilds`.
+pub async fn register_guild_commands(http: &Http, state: &AppState, guild_id: GuildId) {
+    if !state.command_guilds.contains(&guild_id) {
+        return;
+    }
+
+    let settings = state
+        .lookups
+        .settings(state.database.as_ref(), guild_id.get())
+        .await;
+    let commands: Vec<CreateCommand> = COMMANDS
+        .iter()
+        .filter(|command| !settings.is_command_disabled(command.name()))
+        .map(|command| command.register())
+        .collect();
+
+    if let Err(e) = guild_id.set_commands(http, commands).await {
+        eprintln!("Failed to register commands in guild {}: {}", guild_id, e);
+    }
 }
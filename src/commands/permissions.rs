@@ -0,0 +1,341 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, CreateEmbed,
+    EditInteractionResponse, Permissions, ResolvedOption, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::{Command, COMMANDS, PROTECTED_COMMANDS};
+use crate::database::{CommandRule, Database};
+use crate::state::AppState;
+
+/// The permissions a command can be limited to, by option value and name.
+const REQUIRABLE_PERMISSIONS: &[(&str, &str, Permissions)] = &[
+    ("none", "None", Permissions::empty()),
+    (
+        "manage_messages",
+        "Manage Messages",
+        Permissions::MANAGE_MESSAGES,
+    ),
+    (
+        "moderate_members",
+        "Moderate Members",
+        Permissions::MODERATE_MEMBERS,
+    ),
+    (
+        "manage_channels",
+        "Manage Channels",
+        Permissions::MANAGE_CHANNELS,
+    ),
+    ("manage_roles", "Manage Roles", Permissions::MANAGE_ROLES),
+    ("kick_members", "Kick Members", Permissions::KICK_MEMBERS),
+    ("ban_members", "Ban Members", Permissions::BAN_MEMBERS),
+];
+
+/// The most roles a command can be limited to.
+const MAX_ROLES: usize = 10;
+
+/// The longest cooldown, a day.
+const MAX_COOLDOWN_SECONDS: u64 = 86_400;
+
+pub struct CommandPermissions;
+
+#[async_trait]
+impl Command for CommandPermissions {
+    fn name(&self) -> &'static str {
+        "permissions"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s.get(),
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        let (subcommand, options) = match options.first() {
+            Some(ResolvedOption {
+                name,
+                value: ResolvedValue::SubCommand(options),
+                ..
+            }) => (*name, options),
+            _ => return Ok(()),
+        };
+
+        let string_option = |name: &str| {
+            options.iter().find(|opt| opt.name == name).and_then(|opt| {
+                if let ResolvedValue::String(s) = &opt.value {
+                    Some(*s)
+                } else {
+                    None
+                }
+            })
+        };
+
+        let integer_option = |name: &str| {
+            options.iter().find(|opt| opt.name == name).and_then(|opt| {
+                if let ResolvedValue::Integer(i) = opt.value {
+                    Some(i.clamp(0, MAX_COOLDOWN_SECONDS as i64) as u64)
+                } else {
+                    None
+                }
+            })
+        };
+
+        let role_id = options
+            .iter()
+            .find(|opt| opt.name == "role")
+            .and_then(|opt| {
+                if let ResolvedValue::Role(role) = &opt.value {
+                    Some(role.id.get())
+                } else {
+                    None
+                }
+            });
+
+        let name = match (subcommand, string_option("command")) {
+            ("list", _) => return list(ctx, command, state.database.as_ref(), guild_id).await,
+            (_, Some(name)) => name,
+            _ => return Ok(()),
+        };
+
+        if subcommand == "reset" {
+            let content = match state.database.reset_command_rule(guild_id, name).await {
+                Ok(true) => format!("`{}` is open to everyone again.", name),
+                Ok(false) => format!("`{}` had no rule.", name),
+                Err(e) => {
+                    eprintln!("Failed to reset command rule: {}", e);
+                    return database_error(ctx, command).await;
+                }
+            };
+
+            command
+                .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+                .await?;
+            return Ok(());
+        }
+
+        if subcommand != "set" {
+            return Ok(());
+        }
+
+        if PROTECTED_COMMANDS.contains(&name) {
+            command
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .content(format!("`{}` is always open to everyone.", name)),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let mut rule = match state.database.get_command_rule(guild_id, name).await {
+            Ok(rule) => rule.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("Failed to get command rule: {}", e);
+                return database_error(ctx, command).await;
+            }
+        };
+
+        // Only the given options change, roles are added to the ones already required
+        if let Some(role_id) = role_id {
+            if !rule.role_ids.contains(&role_id) {
+                if rule.role_ids.len() >= MAX_ROLES {
+                    let content = format!(
+                        "`{}` already requires {} roles, reset it to start over.",
+                        name, MAX_ROLES
+                    );
+                    command
+                        .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+                        .await?;
+                    return Ok(());
+                }
+                rule.role_ids.push(role_id);
+            }
+        }
+        if let Some(permission) = string_option("permission") {
+            if let Some((_, _, permissions)) = REQUIRABLE_PERMISSIONS
+                .iter()
+                .find(|(value, _, _)| *value == permission)
+            {
+                rule.permissions = permissions.bits();
+            }
+        }
+        if let Some(seconds) = integer_option("user_cooldown") {
+            rule.user_cooldown_seconds = seconds;
+        }
+        if let Some(seconds) = integer_option("channel_cooldown") {
+            rule.channel_cooldown_seconds = seconds;
+        }
+
+        if let Err(e) = state.database.set_command_rule(guild_id, name, &rule).await {
+            eprintln!("Failed to set command rule: {}", e);
+            return database_error(ctx, command).await;
+        }
+
+        command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new().content(format!("`{}`: {}", name, describe(&rule))),
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        let mut permission = CreateCommandOption::new(
+            CommandOptionType::String,
+            "permission",
+            "A permission members need to use the command",
+        );
+        for (value, name, _) in REQUIRABLE_PERMISSIONS {
+            permission = permission.add_string_choice(*name, *value);
+        }
+
+        CreateCommand::new("permissions")
+            .description("Choose who may use each command, and how often.")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "list",
+                "Show the commands with rules",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "set",
+                    "Require a role or permission for a command, or add cooldowns to it",
+                )
+                .add_sub_option(command_option("The command to change"))
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Role,
+                    "role",
+                    "Add a role members can have to use the command",
+                ))
+                .add_sub_option(permission)
+                .add_sub_option(cooldown_option(
+                    "user_cooldown",
+                    "Seconds each member waits between uses, 0 for none",
+                ))
+                .add_sub_option(cooldown_option(
+                    "channel_cooldown",
+                    "Seconds each channel waits between uses, 0 for none",
+                )),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "reset",
+                    "Open a command to everyone again",
+                )
+                .add_sub_option(command_option("The command to reset")),
+            )
+    }
+}
+
+async fn list(
+    ctx: &Context,
+    command: &CommandInteraction,
+    database: &Database,
+    guild_id: u64,
+) -> Result<(), Error> {
+    let rules = match database.get_command_rules(guild_id).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Failed to get command rules: {}", e);
+            return database_error(ctx, command).await;
+        }
+    };
+
+    let description = if rules.is_empty() {
+        "No command has a rule, every member can use them as often as they like.".into()
+    } else {
+        rules
+            .iter()
+            .map(|(name, rule)| format!("`{}`: {}", name, describe(rule)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().embed(
+                CreateEmbed::new()
+                    .title("Command Permissions")
+                    .description(description)
+                    .color(0x5865F2),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Lists who may use a command and how often, in words.
+fn describe(rule: &CommandRule) -> String {
+    let mut parts = Vec::new();
+
+    if !rule.role_ids.is_empty() {
+        let roles: Vec<String> = rule
+            .role_ids
+            .iter()
+            .map(|role_id| format!("<@&{}>", role_id))
+            .collect();
+        parts.push(format!("needs {}", roles.join(" or ")));
+    }
+    if rule.permissions != 0 {
+        let names = Permissions::from_bits_truncate(rule.permissions).get_permission_names();
+        parts.push(format!("needs {}", names.join(", ")));
+    }
+    if rule.user_cooldown_seconds > 0 {
+        parts.push(format!("{}s per member", rule.user_cooldown_seconds));
+    }
+    if rule.channel_cooldown_seconds > 0 {
+        parts.push(format!("{}s per channel", rule.channel_cooldown_seconds));
+    }
+
+    if parts.is_empty() {
+        "open to everyone".into()
+    } else {
+        parts.join(", ")
+    }
+}
+
+async fn database_error(ctx: &Context, command: &CommandInteraction) -> Result<(), Error> {
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().content("Something went wrong, please try again later."),
+        )
+        .await?;
+    Ok(())
+}
+
+fn command_option(description: &str) -> CreateCommandOption {
+    let mut option =
+        CreateCommandOption::new(CommandOptionType::String, "command", description).required(true);
+    for command in COMMANDS
+        .iter()
+        .filter(|command| !PROTECTED_COMMANDS.contains(&command.name()))
+    {
+        option = option.add_string_choice(command.name(), command.name());
+    }
+    option
+}
+
+fn cooldown_option(name: &str, description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::Integer, name, description)
+        .min_int_value(0)
+        .max_int_value(MAX_COOLDOWN_SECONDS)
+}
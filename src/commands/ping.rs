@@ -1,24 +1,43 @@
 use std::time::Instant;
 
 use serenity::all::{CommandInteraction, CreateCommand, EditInteractionResponse};
+use serenity::async_trait;
 use serenity::prelude::*;
 use serenity::Error;
 
-pub async fn execute(ctx: &Context, command: &CommandInteraction) -> Result<(), Error> {
-    command.defer(&ctx.http).await?;
-    let timer_start = Instant::now();
+use crate::commands::Command;
+use crate::state::AppState;
 
-    let content = "Pong!";
-    let builder = EditInteractionResponse::new().content(content);
-    command.edit_response(&ctx.http, builder).await?;
+pub struct Ping;
 
-    let elapsed = (Instant::now() - timer_start).as_millis();
+#[async_trait]
+impl Command for Ping {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
 
-    let builder = EditInteractionResponse::new().content(format!("{} ({}ms)", content, elapsed));
-    command.edit_response(&ctx.http, builder).await?;
-    Ok(())
-}
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        _state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer(&ctx.http).await?;
+        let timer_start = Instant::now();
+
+        let content = "Pong!";
+        let builder = EditInteractionResponse::new().content(content);
+        command.edit_response(&ctx.http, builder).await?;
+
+        let elapsed = (Instant::now() - timer_start).as_millis();
+
+        let builder =
+            EditInteractionResponse::new().content(format!("{} ({}ms)", content, elapsed));
+        command.edit_response(&ctx.http, builder).await?;
+        Ok(())
+    }
 
-pub fn register() -> CreateCommand {
-    CreateCommand::new("ping").description("Check if bot is alive.")
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("ping").description("Check if bot is alive.")
+    }
 }
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateInteractionResponse, CreateMessage,
+    EditInteractionResponse,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::state::AppState;
+
+pub struct Privacy;
+
+#[async_trait]
+impl Command for Privacy {
+    fn name(&self) -> &'static str {
+        "privacy"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer_ephemeral(&ctx.http).await?;
+
+        let user_id = command.user.id.get();
+        let options = &command.data.options();
+
+        let subcommand = match options.first() {
+            Some(option) => option.name,
+            None => return Ok(()),
+        };
+
+        let content = match subcommand {
+            "optout" => match state.database.set_opted_out(user_id, true).await {
+                Ok(_) => {
+                    state.lookups.forget_user(user_id);
+                    "You have opted out. Your messages will no longer be collected or used for generation and `/guess`.\n\
+                    Use `/privacy forget` to also delete what has already been stored."
+                }
+                Err(e) => {
+                    eprintln!("Failed to opt user out: {}", e);
+                    "Something went wrong while opting you out, please try again later."
+                }
+            },
+            "optin" => match state.database.set_opted_out(user_id, false).await {
+                Ok(_) => {
+                    state.lookups.forget_user(user_id);
+                    "You have opted back in. Your messages will be collected again."
+                }
+                Err(e) => {
+                    eprintln!("Failed to opt user in: {}", e);
+                    "Something went wrong while opting you in, please try again later."
+                }
+            },
+            "forget" => return forget(ctx, command, state).await,
+            _ => return Ok(()),
+        };
+
+        command
+            .edit_response(&ctx.http, EditInteractionResponse::new().content(content))
+            .await?;
+        Ok(())
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("privacy")
+            .description("Control how the bot uses your messages.")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "optout",
+                "Stop the bot from collecting your messages",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "optin",
+                "Allow the bot to collect your messages again",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "forget",
+                "Delete everything the bot has stored from you",
+            ))
+    }
+}
+
+async fn forget(
+    ctx: &Context,
+    command: &CommandInteraction,
+    state: &AppState,
+) -> Result<(), Error> {
+    let embed = CreateEmbed::new()
+        .title("Forget Me")
+        .description(
+            "This permanently deletes **every message** the bot has stored from you, \
+            in every server, along with your `/guess` rating and your ratings of generated messages.\n\n\
+            You will also be opted out of future collection. This cannot be undone.",
+        )
+        .color(0xED4245);
+
+    let message = command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new()
+                .embed(embed.clone())
+                .button(
+                    CreateButton::new("confirm")
+                        .style(ButtonStyle::Danger)
+                        .label("Delete my data"),
+                )
+                .button(
+                    CreateButton::new("cancel")
+                        .style(ButtonStyle::Secondary)
+                        .label("Cancel"),
+                ),
+        )
+        .await?;
+
+    let interaction = message
+        .await_component_interaction(&ctx.shard)
+        .author_id(command.user.id)
+        .timeout(Duration::from_secs(60))
+        .await;
+
+    if let Some(interaction) = &interaction {
+        interaction
+            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+            .await?;
+    }
+
+    let confirmed = interaction.is_some_and(|i| i.data.custom_id == "confirm");
+    if !confirmed {
+        command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new()
+                    .content("Nothing was deleted.")
+                    .embeds(vec![])
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let user_id = command.user.id.get();
+    let forgotten = match state.database.forget_user(user_id).await {
+        Ok(forgotten) => {
+            state.lookups.forget_user(user_id);
+            forgotten
+        }
+        Err(e) => {
+            eprintln!("Failed to forget user: {}", e);
+            command
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .content("Something went wrong while deleting your data, please try again later.")
+                        .embeds(vec![])
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let receipt = CreateEmbed::new()
+        .title("Data Deletion Receipt")
+        .description(format!(
+            "**User:** <@{}> (`{}`)\n\
+            **Messages deleted:** {}\n\
+            **Reactions deleted:** {}\n\
+            **Game ratings deleted:** {}\n\
+            **Future collection:** opted out\n\
+            **Completed:** <t:{}:F>",
+            user_id,
+            user_id,
+            forgotten.messages,
+            forgotten.reactions,
+            forgotten.ratings,
+            chrono::Utc::now().timestamp()
+        ))
+        .color(0x57F287);
+
+    command
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new()
+                .embed(receipt.clone())
+                .components(vec![]),
+        )
+        .await?;
+
+    // Keep a copy of the receipt in their DMs, which may be closed
+    if let Err(e) = command
+        .user
+        .direct_message(&ctx.http, CreateMessage::new().embed(receipt))
+        .await
+    {
+        eprintln!("Failed to send deletion receipt: {}", e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,250 @@
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    EditInteractionResponse, ResolvedValue,
+};
+use serenity::async_trait;
+use serenity::prelude::*;
+use serenity::Error;
+
+use crate::commands::Command;
+use crate::database::StoredMessage;
+use crate::state::AppState;
+use crate::utils::visibility;
+
+const RESULTS_PER_PAGE: u64 = 5;
+const MAX_CONTENT_LENGTH: usize = 300;
+
+pub struct Search;
+
+#[async_trait]
+impl Command for Search {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    async fn execute(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        state: &AppState,
+    ) -> Result<(), Error> {
+        command.defer(&ctx.http).await?;
+
+        let guild_id = match command.guild_id {
+            Some(s) => s,
+            _ => return Ok(()),
+        };
+
+        let options = &command.data.options();
+
+        let query = options
+            .iter()
+            .find(|opt| opt.name == "query")
+            .and_then(|opt| {
+                if let ResolvedValue::String(s) = &opt.value {
+                    Some(s.trim())
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        let author_id = options
+            .iter()
+            .find(|opt| opt.name == "user")
+            .and_then(|opt| {
+                if let ResolvedValue::User(user, _) = &opt.value {
+                    Some(user.id.get())
+                } else {
+                    None
+                }
+            });
+
+        let channel_id = options
+            .iter()
+            .find(|opt| opt.name == "channel")
+            .and_then(|opt| {
+                if let ResolvedValue::Channel(channel) = &opt.value {
+                    Some(channel.id.get())
+                } else {
+                    None
+                }
+            });
+
+        if query.is_empty() {
+            command
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .content("Please provide something to search for."),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        // Only search the channels the invoker can read, narrowed down to the one they picked
+        let channel_ids: Vec<u64> = match &command.member {
+            Some(member) => visibility::readable_by_member(&ctx.cache, guild_id, member),
+            None => Vec::new(),
+        }
+        .into_iter()
+        .filter(|&readable| channel_id.is_none() || channel_id == Some(readable))
+        .collect();
+
+        let mut page = 0;
+
+        loop {
+            let (total, messages) = match state
+                .database
+                .search_messages(
+                    guild_id.get(),
+                    query,
+                    author_id,
+                    Some(&channel_ids),
+                    RESULTS_PER_PAGE,
+                    page * RESULTS_PER_PAGE,
+                )
+                .await
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("Failed to search messages: {}", e);
+                    command
+                        .edit_response(
+                            &ctx.http,
+                            EditInteractionResponse::new()
+                                .content("Something went wrong while searching."),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            if total == 0 {
+                command
+                    .edit_response(
+                        &ctx.http,
+                        EditInteractionResponse::new()
+                            .content(format!("No messages found matching `{}`.", query)),
+                    )
+                    .await?;
+                return Ok(());
+            }
+
+            let pages = total.div_ceil(RESULTS_PER_PAGE);
+            let embed = create_embed(query, &messages, page, pages, total);
+
+            let message = command
+                .edit_response(
+                    &ctx.http,
+                    EditInteractionResponse::new()
+                        .embed(embed.clone())
+                        .button(
+                            CreateButton::new("previous")
+                                .style(ButtonStyle::Secondary)
+                                .label("Previous")
+                                .disabled(page == 0),
+                        )
+                        .button(
+                            CreateButton::new("next")
+                                .style(ButtonStyle::Secondary)
+                                .label("Next")
+                                .disabled(page + 1 >= pages),
+                        ),
+                )
+                .await?;
+
+            let interaction = match message
+                .await_component_interaction(&ctx.shard)
+                .author_id(command.user.id)
+                .timeout(Duration::from_secs(120))
+                .await
+            {
+                Some(x) => x,
+                None => {
+                    // Stop paginating, but keep the last page visible
+                    command
+                        .edit_response(
+                            &ctx.http,
+                            EditInteractionResponse::new()
+                                .embed(embed)
+                                .components(vec![]),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            interaction
+                .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+                .await?;
+
+            match interaction.data.custom_id.as_str() {
+                "previous" => page = page.saturating_sub(1),
+                "next" => page = (page + 1).min(pages - 1),
+                _ => {}
+            }
+        }
+    }
+
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new("search")
+            .description("Find who said something.")
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "query", "What to search for")
+                    .required(true),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::User,
+                "user",
+                "Only search this user's messages",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "Only search messages from this channel",
+            ))
+    }
+}
+
+fn create_embed(
+    query: &str,
+    messages: &[StoredMessage],
+    page: u64,
+    pages: u64,
+    total: u64,
+) -> CreateEmbed {
+    let mut description = String::new();
+
+    for message in messages {
+        let mut content: String = message.content.chars().take(MAX_CONTENT_LENGTH).collect();
+        if content.len() < message.content.len() {
+            content.push_str("...");
+        }
+
+        description.push_str(&format!(
+            "<@{}> in <#{}> — [Jump](https://discord.com/channels/{}/{}/{})\n> {}\n\n",
+            message.author_id,
+            message.channel_id,
+            message.guild_id,
+            message.channel_id,
+            message.message_id,
+            content.replace('\n', "\n> ")
+        ));
+    }
+
+    CreateEmbed::new()
+        .title(format!("Search results for \"{}\"", query))
+        .description(description.trim_end())
+        .color(0x5865F2)
+        .footer(CreateEmbedFooter::new(format!(
+            "Page {} of {} • {} matches",
+            page + 1,
+            pages,
+            total
+        )))
+}
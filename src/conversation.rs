@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serenity::all::ChannelId;
+use tokio::time::{Duration, Instant};
+
+/// A conversation members are having with the bot in a channel, by replying
+/// to its messages.
+struct Conversation {
+    turns: u64,
+    last_turn: Instant,
+    said: Vec<String>,
+}
+
+/// The conversation going on in each channel.
+#[derive(Default)]
+pub struct Conversations {
+    channels: Mutex<HashMap<ChannelId, Conversation>>,
+}
+
+impl Conversations {
+    /// Takes the bot's next turn in the channel's conversation, starting a new
+    /// one if nobody replied for `timeout`. Returns what the bot already said in
+    /// it, or `None` once it has had `max_turns` turns.
+    pub fn take_turn(
+        &self,
+        channel_id: ChannelId,
+        max_turns: u64,
+        timeout: Duration,
+    ) -> Option<Vec<String>> {
+        let mut channels = self.channels.lock().unwrap();
+
+        // Conversations that went quiet are over, forget them
+        channels.retain(|_, conversation| conversation.last_turn.elapsed() < timeout);
+
+        let conversation = channels.entry(channel_id).or_insert(Conversation {
+            turns: 0,
+            last_turn: Instant::now(),
+            said: Vec::new(),
+        });
+
+        if conversation.turns >= max_turns {
+            return None;
+        }
+
+        conversation.turns += 1;
+        conversation.last_turn = Instant::now();
+        Some(conversation.said.clone())
+    }
+
+    /// Remembers what the bot said in the channel's conversation, so it isn't repeated.
+    pub fn said(&self, channel_id: ChannelId, message: String) {
+        if let Some(conversation) = self.channels.lock().unwrap().get_mut(&channel_id) {
+            conversation.said.push(message);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serenity::all::{ChannelId, GuildId, UserId};
+use tokio::time::{Duration, Instant};
+
+/// Who a cooldown holds back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Scope {
+    Member(GuildId, UserId),
+    Channel(ChannelId),
+}
+
+/// Why a command can't be used yet, with how long is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cooldown {
+    Member(Duration),
+    Channel(Duration),
+}
+
+/// Until when each command is cooling down, for each member and channel.
+#[derive(Default)]
+pub struct Cooldowns {
+    until: Mutex<HashMap<(String, Scope), Instant>>,
+}
+
+impl Cooldowns {
+    /// Records a use of `command` and starts its cooldowns, unless the member
+    /// or the channel is still cooling down from an earlier use.
+    pub fn try_use(
+        &self,
+        command: &str,
+        guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+        member_cooldown: Duration,
+        channel_cooldown: Duration,
+    ) -> Result<(), Cooldown> {
+        let mut until = self.until.lock().unwrap();
+        let now = Instant::now();
+
+        // Cooldowns that ran out are forgotten
+        until.retain(|_, until| *until > now);
+
+        let member = (command.to_string(), Scope::Member(guild_id, user_id));
+        if let Some(until) = until.get(&member) {
+            return Err(Cooldown::Member(*until - now));
+        }
+
+        let channel = (command.to_string(), Scope::Channel(channel_id));
+        if let Some(until) = until.get(&channel) {
+            return Err(Cooldown::Channel(*until - now));
+        }
+
+        if !member_cooldown.is_zero() {
+            until.insert(member, now + member_cooldown);
+        }
+        if !channel_cooldown.is_zero() {
+            until.insert(channel, now + channel_cooldown);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+mod postgres;
+mod sqlite;
+
+use futures::stream::BoxStream;
+use serenity::async_trait;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+pub use postgres::PostgresDatabase;
+pub use sqlite::SqliteDatabase;
+
+/// The storage backend the bot was started with.
+pub type Database = dyn Storage;
+
+/// A message row as it is stored in the `messages` table.
+#[derive(Debug, Clone)]
+pub struct StoredMessage {
+    pub message_id: u64,
+    pub author_id: u64,
+    pub channel_id: u64,
+    pub guild_id: u64,
+    pub content: String,
+    /// Whether the message was sent by a bot or a webhook.
+    pub is_bot: bool,
+    /// The channel the thread it was sent in belongs to, if it was sent in one.
+    pub parent_id: Option<u64>,
+}
+
+/// What the bot may do with a channel. Channels that were never configured allow everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelFlags {
+    /// Store new messages sent in the channel.
+    pub collect: bool,
+    /// Use the channel's messages for generation and `/guess`.
+    pub generate_from: bool,
+    /// Let the autoposter post into the channel.
+    pub post_into: bool,
+}
+
+impl ChannelFlags {
+    /// The bot neither reads nor writes the channel.
+    pub const IGNORE: Self = Self {
+        collect: false,
+        generate_from: false,
+        post_into: false,
+    };
+}
+
+impl Default for ChannelFlags {
+    fn default() -> Self {
+        Self {
+            collect: true,
+            generate_from: true,
+            post_into: true,
+        }
+    }
+}
+
+/// Who may use a command in a guild, and how often. Commands without a rule
+/// are open to everyone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandRule {
+    /// Members need one of these roles, unless there are none.
+    pub role_ids: Vec<u64>,
+    /// Members need all of these permissions, as Discord permission bits.
+    pub permissions: u64,
+    /// How long a member waits between uses.
+    pub user_cooldown_seconds: u64,
+    /// How long a channel waits between uses, by anyone.
+    pub channel_cooldown_seconds: u64,
+}
+
+/// What `Storage::forget_user` deleted about a user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ForgottenUser {
+    pub messages: u64,
+    /// Reactions to generated messages.
+    pub reactions: u64,
+    /// Game ratings, there is at most one.
+    pub ratings: u64,
+}
+
+/// A message the bot generated and posted, as logged in the `generations` table.
+#[derive(Debug, Clone)]
+pub struct Generation {
+    /// The id of the posted message.
+    pub message_id: u64,
+    pub guild_id: u64,
+    /// The channel it was posted into.
+    pub channel_id: u64,
+    /// The channel whose history it was generated from.
+    pub source_channel_id: u64,
+    pub content: String,
+    /// The word generation started from, if there was one.
+    pub seed: Option<String>,
+    /// The settings it was generated with, as a JSON object.
+    pub settings: String,
+    /// When it was posted, as a unix timestamp.
+    pub created_at: i64,
+    /// How many members liked it minus how many disliked it, ignored when logging it.
+    pub score: i64,
+    /// When it was reposted to the hall of fame, as a unix timestamp, ignored when logging it.
+    pub featured_at: Option<i64>,
+}
+
+/// Narrows down which messages of a guild are returned by `Storage::stream_messages`.
+///
+/// `since` and `until` are message snowflakes, `until` being exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter {
+    pub guild_id: u64,
+    pub channel_id: Option<u64>,
+    pub author_id: Option<u64>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+/// Connects to the database at `database_url`, picking the backend from its
+/// scheme. `postgres://` and `postgresql://` URLs use PostgreSQL, anything
+/// else is treated as SQLite.
+pub async fn connect(database_url: &str) -> Result<Arc<Database>, sqlx::Error> {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        Ok(Arc::new(PostgresDatabase::new(database_url).await?))
+    } else {
+        Ok(Arc::new(SqliteDatabase::new(database_url).await?))
+    }
+}
+
+/// Everything the bot reads from and writes to its database.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Inserts a batch of messages in a single transaction. Consent is checked
+    /// again as they are written, so messages of authors who opted out since
+    /// they were queued are skipped.
+    async fn insert_messages(&self, messages: &[StoredMessage]) -> Result<(), sqlx::Error>;
+
+    /// Returns a uniform random sample of up to `limit` messages from a channel.
+    /// Bot and webhook messages are skipped unless `include_bots` is set, and
+    /// nothing is returned if the channel may not be generated from.
+    async fn get_messages_for_markov(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        blacklist_prefixes: &[&str],
+        include_bots: bool,
+        limit: usize,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Returns a random sample of up to `limit` messages a member sent in a
+    /// guild, from the channels that may be generated from. Only messages from
+    /// `channel_ids` are sampled when given, and nothing is returned for
+    /// members who opted out.
+    async fn get_author_messages_for_markov(
+        &self,
+        guild_id: u64,
+        author_id: u64,
+        blacklist_prefixes: &[&str],
+        include_bots: bool,
+        channel_ids: Option<&[u64]>,
+        limit: usize,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Streams every message matching `filter` in insertion order, without
+    /// loading the whole result set into memory.
+    fn stream_messages(
+        &self,
+        filter: &MessageFilter,
+    ) -> BoxStream<'_, Result<StoredMessage, sqlx::Error>>;
+
+    /// Searches a guild's messages for `query`, treated as a phrase, ranked by relevance.
+    /// Channels that may not be generated from are skipped, and only messages
+    /// from `channel_ids` are searched when given.
+    ///
+    /// Returns the total amount of matches, and the requested page of them.
+    async fn search_messages(
+        &self,
+        guild_id: u64,
+        query: &str,
+        author_id: Option<u64>,
+        channel_ids: Option<&[u64]>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<(u64, Vec<StoredMessage>), sqlx::Error>;
+
+    /// Returns the channel with the most stored messages, among those that may be
+    /// both generated from and posted into, or 0 if there is none. Only
+    /// `channel_ids` are considered when given.
+    async fn get_most_popular_channel(
+        &self,
+        guild_id: u64,
+        channel_ids: Option<&[u64]>,
+    ) -> Result<u64, sqlx::Error>;
+
+    async fn get_messages_for_leaderboard(
+        &self,
+        guild_id: u64,
+        member_id: Option<u64>,
+    ) -> Result<Vec<(String, u64)>, sqlx::Error>;
+
+    /// Returns a uniformly picked random message of a guild, with its author.
+    /// Bot and webhook messages are skipped unless `include_bots` is set, as
+    /// are channels that may not be generated from. Only messages from
+    /// `channel_ids` are picked when given. Lookups are random and bounded, so
+    /// nothing may be found when very few messages match.
+    async fn get_random_message(
+        &self,
+        guild_id: u64,
+        min_letters_amount: u64,
+        prefix_list: &[&str],
+        include_bots: bool,
+        channel_ids: Option<&[u64]>,
+    ) -> Result<Option<(String, u64)>, sqlx::Error>;
+
+    async fn get_user_rating(&self, user_id: u64) -> Result<Option<f32>, sqlx::Error>;
+
+    async fn insert_initial_rating(&self, user_id: u64, rating: f32) -> Result<(), sqlx::Error>;
+
+    async fn update_user_rating(&self, user_id: u64, rating: f32) -> Result<(), sqlx::Error>;
+
+    async fn is_opted_out(&self, user_id: u64) -> Result<bool, sqlx::Error>;
+
+    async fn set_opted_out(&self, user_id: u64, opted_out: bool) -> Result<(), sqlx::Error>;
+
+    /// Deletes every stored message, reaction to a generation and the game
+    /// rating of a user, and opts them out so nothing new is collected.
+    ///
+    /// Returns how much of each was deleted.
+    async fn forget_user(&self, user_id: u64) -> Result<ForgottenUser, sqlx::Error>;
+
+    /// Returns every setting a guild has changed from its default.
+    async fn get_guild_settings(
+        &self,
+        guild_id: u64,
+    ) -> Result<HashMap<String, String>, sqlx::Error>;
+
+    async fn set_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+        value: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Resets one setting of a guild, or all of them if `key` is `None`.
+    async fn reset_guild_settings(
+        &self,
+        guild_id: u64,
+        key: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the flags of a channel. Threads that were never configured take
+    /// those of `parent_id`, and everything else not configured the defaults.
+    async fn get_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        parent_id: Option<u64>,
+    ) -> Result<ChannelFlags, sqlx::Error>;
+
+    /// Returns every configured channel of a guild with its flags.
+    async fn get_guild_channel_flags(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<(u64, ChannelFlags)>, sqlx::Error>;
+
+    async fn set_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        flags: ChannelFlags,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Resets a channel to the defaults, returning whether it was configured.
+    async fn reset_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Returns the rule of a command in a guild, if one was set.
+    async fn get_command_rule(
+        &self,
+        guild_id: u64,
+        command: &str,
+    ) -> Result<Option<CommandRule>, sqlx::Error>;
+
+    /// Returns every command of a guild with a rule.
+    async fn get_command_rules(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<(String, CommandRule)>, sqlx::Error>;
+
+    async fn set_command_rule(
+        &self,
+        guild_id: u64,
+        command: &str,
+        rule: &CommandRule,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Removes the rule of a command, returning whether it had one.
+    async fn reset_command_rule(&self, guild_id: u64, command: &str) -> Result<bool, sqlx::Error>;
+
+    /// Returns the guilds that have changed at least one setting.
+    async fn get_configured_guilds(&self) -> Result<Vec<u64>, sqlx::Error>;
+
+    /// Deletes a guild's messages sent before the `before` snowflake, returning how many were deleted.
+    async fn prune_messages_before(&self, guild_id: u64, before: u64) -> Result<u64, sqlx::Error>;
+
+    /// Deletes the oldest messages of every channel in a guild that has more
+    /// than `max_rows`, returning how many were deleted.
+    async fn prune_channel_overflow(
+        &self,
+        guild_id: u64,
+        max_rows: u64,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Refreshes the query planner statistics, and reclaims free space if `vacuum` is set.
+    async fn optimize(&self, vacuum: bool) -> Result<(), sqlx::Error>;
+
+    /// Writes a consistent snapshot of the database to `destination`, which must not exist yet.
+    async fn backup(&self, destination: &Path) -> Result<(), sqlx::Error>;
+
+    /// Marks every stored message of `author_id` as sent by a bot, returning how many changed.
+    async fn flag_bot_author(&self, author_id: u64) -> Result<u64, sqlx::Error>;
+
+    /// Schedules a guild's data to be purged at `purge_after` (a unix timestamp).
+    async fn schedule_guild_purge(
+        &self,
+        guild_id: u64,
+        purge_after: i64,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Cancels a scheduled purge, returning whether one was pending.
+    async fn cancel_guild_purge(&self, guild_id: u64) -> Result<bool, sqlx::Error>;
+
+    /// Returns every scheduled purge as `(guild_id, purge_after)`.
+    async fn get_pending_guild_purges(&self) -> Result<Vec<(u64, i64)>, sqlx::Error>;
+
+    /// Returns every guild with stored messages, along with how many are stored.
+    async fn get_stored_guilds(&self) -> Result<Vec<(u64, u64)>, sqlx::Error>;
+
+    /// Deletes all messages, settings, channel flags, command rules and generations of a guild,
+    /// returning how many messages were deleted.
+    async fn purge_guild(&self, guild_id: u64) -> Result<u64, sqlx::Error>;
+
+    /// Logs a generation. Logging a message again, such as after it was
+    /// regenerated, replaces its content, seed and settings.
+    async fn log_generation(&self, generation: &Generation) -> Result<(), sqlx::Error>;
+
+    /// Forgets a generation and the reactions to it, such as when its message was deleted.
+    async fn delete_generation(&self, message_id: u64) -> Result<(), sqlx::Error>;
+
+    /// Records a user's reaction to a logged generation, returning the generation
+    /// with its new score if the message is one. A member counts once, however
+    /// many emoji they react with.
+    async fn add_generation_reaction(
+        &self,
+        message_id: u64,
+        user_id: u64,
+        emoji: &str,
+        value: i64,
+    ) -> Result<Option<Generation>, sqlx::Error>;
+
+    /// Forgets the reactions to a generation, only those of `user_id` and
+    /// with `emoji` when given.
+    async fn remove_generation_reactions(
+        &self,
+        message_id: u64,
+        user_id: Option<u64>,
+        emoji: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the content and score of a guild's most recent generations that
+    /// were reacted to, newest first.
+    async fn get_generation_feedback(
+        &self,
+        guild_id: u64,
+        limit: u64,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error>;
+
+    /// Returns a guild's best scored generations, or its worst if `best` isn't
+    /// set. Only generations scored above zero, or below it, are returned.
+    async fn get_ranked_generations(
+        &self,
+        guild_id: u64,
+        best: bool,
+        limit: u64,
+    ) -> Result<Vec<Generation>, sqlx::Error>;
+
+    /// Marks a generation as reposted to the hall of fame at `featured_at`,
+    /// returning false if it already was.
+    async fn feature_generation(
+        &self,
+        message_id: u64,
+        featured_at: i64,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Takes a generation out of the hall of fame, such as when reposting it failed.
+    async fn unfeature_generation(&self, message_id: u64) -> Result<(), sqlx::Error>;
+
+    /// Returns how many generations of a guild are in the hall of fame, and
+    /// the requested page of them, most recently featured first.
+    async fn get_featured_generations(
+        &self,
+        guild_id: u64,
+        limit: u64,
+        offset: u64,
+    ) -> Result<(u64, Vec<Generation>), sqlx::Error>;
+}
@@ -0,0 +1,1445 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use futures::stream::{BoxStream, StreamExt};
+use serenity::async_trait;
+use sqlx::postgres::{PgPool as Pool, PgPoolOptions, PgRow};
+use sqlx::{Postgres, QueryBuilder, Row};
+
+use super::{
+    ChannelFlags, CommandRule, ForgottenUser, Generation, MessageFilter, Storage, StoredMessage,
+};
+use crate::utils::sampling;
+
+/// Maximum amount of rows deleted per statement while pruning.
+const PRUNE_BATCH_SIZE: i64 = 5000;
+
+pub struct PostgresDatabase {
+    pool: Pool,
+}
+
+impl PostgresDatabase {
+    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(16)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect(database_url)
+            .await?;
+
+        // Create tables if they don't exist
+        Self::setup_tables(&pool).await?;
+
+        Ok(PostgresDatabase { pool })
+    }
+
+    async fn setup_tables(pool: &Pool) -> Result<(), sqlx::Error> {
+        // Create messages table, `content_tsv` backs the full-text search
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS messages (
+                id BIGSERIAL PRIMARY KEY,
+                message_id BIGINT NOT NULL,
+                author_id BIGINT NOT NULL,
+                channel_id BIGINT NOT NULL,
+                guild_id BIGINT NOT NULL,
+                content TEXT NOT NULL,
+                channel_seq BIGINT,
+                guild_seq BIGINT,
+                content_tsv TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', content)) STORED
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Bot and webhook messages, kept out of generation unless a guild opts in
+        sqlx::query(
+            "ALTER TABLE messages ADD COLUMN IF NOT EXISTS is_bot BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .execute(pool)
+        .await?;
+
+        // The parent channel of messages sent in threads, whose flags they share
+        sqlx::query("ALTER TABLE messages ADD COLUMN IF NOT EXISTS parent_id BIGINT")
+            .execute(pool)
+            .await?;
+
+        // Create indexes for performance
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_guild_channel ON messages (guild_id, channel_id)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_guild_author ON messages (guild_id, author_id)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_guild ON messages (guild_id)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_channel_seq ON messages (guild_id, channel_id, channel_seq)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_messages_guild_seq ON messages (guild_id, guild_seq)",
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_messages_content_tsv ON messages USING GIN (content_tsv)",
+        )
+        .execute(pool)
+        .await?;
+
+        // One row per guild, locked while its messages are numbered so concurrent
+        // inserts can't hand out the same sequence numbers
+        sqlx::query("CREATE TABLE IF NOT EXISTS guild_seq_locks (guild_id BIGINT PRIMARY KEY)")
+            .execute(pool)
+            .await?;
+
+        // Create game_ratings table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS game_ratings (
+                user_id TEXT PRIMARY KEY,
+                rating REAL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create guild_settings table, holds only the values changed from their defaults
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS guild_settings (
+                guild_id BIGINT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (guild_id, key)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // The autopost wait used to be a range, carry it over as the interval
+        // in its middle give or take half its width (the old defaults, 300 and
+        // 900, are the new ones). Settings already in the new form are kept
+        sqlx::query(
+            r#"
+            WITH legacy AS (
+                SELECT guild_id, LEAST(low, high) AS low, GREATEST(low, high) AS high
+                FROM (
+                    SELECT guild_id,
+                        COALESCE(MAX(CASE WHEN key = 'autopost_min_interval_seconds' THEN CAST(value AS BIGINT) END), 300) AS low,
+                        COALESCE(MAX(CASE WHEN key = 'autopost_max_interval_seconds' THEN CAST(value AS BIGINT) END), 900) AS high
+                    FROM guild_settings
+                    WHERE key IN ('autopost_min_interval_seconds', 'autopost_max_interval_seconds')
+                    GROUP BY guild_id
+                ) AS stored
+            )
+            INSERT INTO guild_settings (guild_id, key, value)
+            SELECT guild_id, 'autopost_interval_seconds', CAST((low + high) / 2 AS TEXT) FROM legacy
+            UNION ALL
+            SELECT guild_id, 'autopost_jitter_seconds', CAST((high - low) / 2 AS TEXT) FROM legacy
+            ON CONFLICT (guild_id, key) DO NOTHING
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            "DELETE FROM guild_settings WHERE key IN ('autopost_min_interval_seconds', 'autopost_max_interval_seconds')",
+        )
+        .execute(pool)
+        .await?;
+
+        // Create channel_flags table, channels without a row allow everything
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS channel_flags (
+                guild_id BIGINT NOT NULL,
+                channel_id BIGINT NOT NULL,
+                collect BOOLEAN NOT NULL,
+                generate_from BOOLEAN NOT NULL,
+                post_into BOOLEAN NOT NULL,
+                PRIMARY KEY (guild_id, channel_id)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create command_rules table, commands without a row are open to everyone
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS command_rules (
+                guild_id BIGINT NOT NULL,
+                command TEXT NOT NULL,
+                role_ids TEXT NOT NULL,
+                permissions BIGINT NOT NULL,
+                user_cooldown_seconds BIGINT NOT NULL,
+                channel_cooldown_seconds BIGINT NOT NULL,
+                PRIMARY KEY (guild_id, command)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create pending_guild_purges table, for guilds the bot was removed from
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS pending_guild_purges (
+                guild_id BIGINT PRIMARY KEY,
+                purge_after BIGINT NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create user_consent table, users listed here have opted out of collection
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_consent (
+                user_id BIGINT PRIMARY KEY,
+                opted_out BOOLEAN NOT NULL DEFAULT FALSE
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create generations table, every message the bot generated and posted
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS generations (
+                message_id BIGINT PRIMARY KEY,
+                guild_id BIGINT NOT NULL,
+                channel_id BIGINT NOT NULL,
+                source_channel_id BIGINT NOT NULL,
+                content TEXT NOT NULL,
+                seed TEXT,
+                settings TEXT NOT NULL,
+                created_at BIGINT NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_generations_guild ON generations (guild_id, created_at)")
+            .execute(pool)
+            .await?;
+
+        // When a generation was reposted to the hall of fame
+        sqlx::query("ALTER TABLE generations ADD COLUMN IF NOT EXISTS featured_at BIGINT")
+            .execute(pool)
+            .await?;
+
+        // Create generation_reactions table, the feedback members gave on generations
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS generation_reactions (
+                message_id BIGINT NOT NULL,
+                user_id BIGINT NOT NULL,
+                emoji TEXT NOT NULL,
+                value BIGINT NOT NULL,
+                PRIMARY KEY (message_id, user_id, emoji)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // One vote per member and generation, however many emoji they reacted with
+        sqlx::query(
+            r#"
+            CREATE OR REPLACE VIEW generation_votes AS
+            SELECT message_id, user_id, GREATEST(-1, LEAST(1, SUM(value)))::BIGINT AS vote
+            FROM generation_reactions
+            GROUP BY message_id, user_id
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Leaves out the messages of channels whose flags match `excluded`, such as
+/// `NOT generate_from`, in the guild bound at `guild_id`. Threads without flags
+/// of their own take those of their parent channel, as in `get_channel_flags`.
+fn flags_filter(guild_id: &str, excluded: &str) -> String {
+    format!(
+        "channel_id NOT IN (SELECT channel_id FROM channel_flags WHERE guild_id = {guild_id} AND {excluded}) \
+        AND (parent_id IS NULL \
+            OR parent_id NOT IN (SELECT channel_id FROM channel_flags WHERE guild_id = {guild_id} AND {excluded}) \
+            OR channel_id IN (SELECT channel_id FROM channel_flags WHERE guild_id = {guild_id}))"
+    )
+}
+
+fn channel_flags(row: &PgRow) -> ChannelFlags {
+    ChannelFlags {
+        collect: row.get("collect"),
+        generate_from: row.get("generate_from"),
+        post_into: row.get("post_into"),
+    }
+}
+
+fn command_rule(row: &PgRow) -> CommandRule {
+    CommandRule {
+        role_ids: row
+            .get::<String, _>("role_ids")
+            .split_whitespace()
+            .filter_map(|role_id| role_id.parse().ok())
+            .collect(),
+        permissions: row.get::<i64, _>("permissions") as u64,
+        user_cooldown_seconds: row.get::<i64, _>("user_cooldown_seconds") as u64,
+        channel_cooldown_seconds: row.get::<i64, _>("channel_cooldown_seconds") as u64,
+    }
+}
+
+fn generation(row: &PgRow) -> Generation {
+    Generation {
+        message_id: row.get::<i64, _>("message_id") as u64,
+        guild_id: row.get::<i64, _>("guild_id") as u64,
+        channel_id: row.get::<i64, _>("channel_id") as u64,
+        source_channel_id: row.get::<i64, _>("source_channel_id") as u64,
+        content: row.get("content"),
+        seed: row.get("seed"),
+        settings: row.get("settings"),
+        created_at: row.get("created_at"),
+        score: row.get("score"),
+        featured_at: row.get("featured_at"),
+    }
+}
+
+fn stored_message(row: &PgRow) -> StoredMessage {
+    StoredMessage {
+        message_id: row.get::<i64, _>("message_id") as u64,
+        author_id: row.get::<i64, _>("author_id") as u64,
+        channel_id: row.get::<i64, _>("channel_id") as u64,
+        guild_id: row.get::<i64, _>("guild_id") as u64,
+        content: row.get("content"),
+        is_bot: row.get("is_bot"),
+        parent_id: row.get::<Option<i64>, _>("parent_id").map(|id| id as u64),
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresDatabase {
+    async fn insert_messages(&self, messages: &[StoredMessage]) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        // Lock the guilds in a fixed order so two batches can't deadlock on each other
+        let mut guild_ids: Vec<i64> = messages.iter().map(|m| m.guild_id as i64).collect();
+        guild_ids.sort_unstable();
+        guild_ids.dedup();
+        for guild_id in guild_ids {
+            sqlx::query(
+                "INSERT INTO guild_seq_locks (guild_id) VALUES ($1) ON CONFLICT DO NOTHING",
+            )
+            .bind(guild_id)
+            .execute(&mut *transaction)
+            .await?;
+
+            sqlx::query("SELECT guild_id FROM guild_seq_locks WHERE guild_id = $1 FOR UPDATE")
+                .bind(guild_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        // Holds off `forget_user` for these authors until the batch is committed,
+        // and waits for one that is already running, so its opt-out is seen below
+        let mut author_ids: Vec<i64> = messages.iter().map(|m| m.author_id as i64).collect();
+        author_ids.sort_unstable();
+        author_ids.dedup();
+        for author_id in author_ids {
+            sqlx::query("SELECT pg_advisory_xact_lock_shared($1)")
+                .bind(author_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for message in messages {
+            sqlx::query(
+                r#"
+                INSERT INTO messages (message_id, author_id, channel_id, guild_id, content, is_bot, parent_id, channel_seq, guild_seq)
+                SELECT $1, $2, $3, $4, $5, $6, $7,
+                    COALESCE((SELECT MAX(channel_seq) FROM messages WHERE guild_id = $4 AND channel_id = $3), -1) + 1,
+                    COALESCE((SELECT MAX(guild_seq) FROM messages WHERE guild_id = $4), -1) + 1
+                WHERE NOT EXISTS (SELECT 1 FROM user_consent WHERE user_id = $2 AND opted_out)
+                "#,
+            )
+            .bind(message.message_id as i64)
+            .bind(message.author_id as i64)
+            .bind(message.channel_id as i64)
+            .bind(message.guild_id as i64)
+            .bind(&message.content)
+            .bind(message.is_bot)
+            .bind(message.parent_id.map(|id| id as i64))
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_messages_for_markov(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        blacklist_prefixes: &[&str],
+        include_bots: bool,
+        limit: usize,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let is_allowed = move |content: &String| {
+            !blacklist_prefixes
+                .iter()
+                .any(|&prefix| content.starts_with(prefix))
+        };
+
+        let range = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
+            "SELECT MIN(channel_seq), MAX(channel_seq) FROM messages WHERE guild_id = $1 AND channel_id = $2",
+        )
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let (lo, hi) = match range {
+            (Some(lo), Some(hi)) => (lo, hi),
+            _ => return Ok(Vec::new()),
+        };
+
+        sampling::sample(lo, hi, limit, 0, |positions| async move {
+            let mut query =
+                QueryBuilder::<Postgres>::new("SELECT content FROM messages WHERE guild_id = ");
+            query
+                .push_bind(guild_id as i64)
+                .push(" AND channel_id = ")
+                .push_bind(channel_id as i64)
+                .push(" AND LENGTH(content) > 10 AND author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out)");
+            if !include_bots {
+                query.push(" AND NOT is_bot");
+            }
+            // The guild id is the first bind
+            query
+                .push(" AND ")
+                .push(flags_filter("$1", "NOT generate_from"));
+            query
+                .push(" AND channel_seq = ANY(")
+                .push_bind(positions)
+                .push(")");
+
+            let rows = query.build().fetch_all(&self.pool).await?;
+            Ok(rows
+                .iter()
+                .map(|row| row.get::<String, _>("content"))
+                .filter(is_allowed)
+                .collect())
+        })
+        .await
+    }
+
+    async fn get_author_messages_for_markov(
+        &self,
+        guild_id: u64,
+        author_id: u64,
+        blacklist_prefixes: &[&str],
+        include_bots: bool,
+        channel_ids: Option<&[u64]>,
+        limit: usize,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let channel_ids: Option<Vec<i64>> =
+            channel_ids.map(|ids| ids.iter().map(|&id| id as i64).collect());
+
+        // A member's messages are scattered over the guild's ordinals, so find
+        // theirs first and only fetch the contents of the sampled ones
+        let positions = sqlx::query_scalar::<_, i64>(&format!(
+            r#"
+            SELECT guild_seq FROM messages
+            WHERE guild_id = $1
+                AND author_id = $2
+                AND LENGTH(content) > 10
+                AND author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out)
+                AND ($3 OR NOT is_bot)
+                AND {}
+                AND ($4::BIGINT[] IS NULL OR channel_id = ANY($4))
+            "#,
+            flags_filter("$1", "NOT generate_from")
+        ))
+        .bind(guild_id as i64)
+        .bind(author_id as i64)
+        .bind(include_bots)
+        .bind(channel_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        sampling::sample_known(positions, limit, |positions| async move {
+            let rows = sqlx::query(
+                "SELECT content FROM messages WHERE guild_id = $1 AND guild_seq = ANY($2)",
+            )
+            .bind(guild_id as i64)
+            .bind(positions)
+            .fetch_all(&self.pool)
+            .await?;
+
+            Ok(rows
+                .iter()
+                .map(|row| row.get::<String, _>("content"))
+                .filter(|content| {
+                    !blacklist_prefixes
+                        .iter()
+                        .any(|&prefix| content.starts_with(prefix))
+                })
+                .collect())
+        })
+        .await
+    }
+
+    fn stream_messages(
+        &self,
+        filter: &MessageFilter,
+    ) -> BoxStream<'_, Result<StoredMessage, sqlx::Error>> {
+        sqlx::query(
+            r#"
+            SELECT message_id, author_id, channel_id, guild_id, content, is_bot, parent_id FROM messages
+            WHERE guild_id = $1
+                AND ($2::BIGINT IS NULL OR channel_id = $2)
+                AND ($3::BIGINT IS NULL OR author_id = $3)
+                AND ($4::BIGINT IS NULL OR message_id >= $4)
+                AND ($5::BIGINT IS NULL OR message_id < $5)
+            ORDER BY id
+            "#,
+        )
+        .bind(filter.guild_id as i64)
+        .bind(filter.channel_id.map(|id| id as i64))
+        .bind(filter.author_id.map(|id| id as i64))
+        .bind(filter.since.map(|id| id as i64))
+        .bind(filter.until.map(|id| id as i64))
+        .fetch(&self.pool)
+        .map(|row| row.map(|row| stored_message(&row)))
+        .boxed()
+    }
+
+    async fn search_messages(
+        &self,
+        guild_id: u64,
+        query: &str,
+        author_id: Option<u64>,
+        channel_ids: Option<&[u64]>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<(u64, Vec<StoredMessage>), sqlx::Error> {
+        let channel_ids: Option<Vec<i64>> =
+            channel_ids.map(|ids| ids.iter().map(|&id| id as i64).collect());
+
+        // `phraseto_tsquery` takes plain text, so user input needs no escaping
+        let filter = format!(
+            r#"
+            WHERE content_tsv @@ phraseto_tsquery('simple', $1)
+                AND guild_id = $2
+                AND ($3::BIGINT IS NULL OR author_id = $3)
+                AND ($4::BIGINT[] IS NULL OR channel_id = ANY($4))
+                AND author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out)
+                AND {}
+            "#,
+            flags_filter("$2", "NOT generate_from")
+        );
+
+        let total =
+            sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM messages {filter}"))
+                .bind(query)
+                .bind(guild_id as i64)
+                .bind(author_id.map(|id| id as i64))
+                .bind(&channel_ids)
+                .fetch_one(&self.pool)
+                .await?;
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT message_id, author_id, channel_id, guild_id, content, is_bot, parent_id FROM messages
+            {filter}
+            ORDER BY ts_rank(content_tsv, phraseto_tsquery('simple', $1)) DESC
+            LIMIT $5 OFFSET $6
+            "#
+        ))
+        .bind(query)
+        .bind(guild_id as i64)
+        .bind(author_id.map(|id| id as i64))
+        .bind(&channel_ids)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let messages = rows.iter().map(stored_message).collect();
+
+        Ok((total as u64, messages))
+    }
+
+    async fn get_most_popular_channel(
+        &self,
+        guild_id: u64,
+        channel_ids: Option<&[u64]>,
+    ) -> Result<u64, sqlx::Error> {
+        let channel_ids: Option<Vec<i64>> =
+            channel_ids.map(|ids| ids.iter().map(|&id| id as i64).collect());
+
+        let row = sqlx::query(&format!(
+            "SELECT channel_id FROM messages WHERE guild_id = $1 AND {} AND ($2::BIGINT[] IS NULL OR channel_id = ANY($2)) GROUP BY channel_id ORDER BY COUNT(*) DESC LIMIT 1",
+            flags_filter("$1", "(NOT generate_from OR NOT post_into)")
+        ))
+        .bind(guild_id as i64)
+        .bind(channel_ids)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(row.get::<i64, _>("channel_id") as u64),
+            None => Ok(0),
+        }
+    }
+
+    async fn get_messages_for_leaderboard(
+        &self,
+        guild_id: u64,
+        member_id: Option<u64>,
+    ) -> Result<Vec<(String, u64)>, sqlx::Error> {
+        // Add LIMIT to prevent memory issues on large servers
+        const MAX_MESSAGES: i64 = 50000; // Reasonable limit for processing
+
+        let rows = sqlx::query(
+            "SELECT content, author_id FROM messages WHERE guild_id = $1 AND ($2::BIGINT IS NULL OR author_id = $2) LIMIT $3",
+        )
+        .bind(guild_id as i64)
+        .bind(member_id.map(|id| id as i64))
+        .bind(MAX_MESSAGES)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let messages: Vec<(String, u64)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<String, _>("content"),
+                    row.get::<i64, _>("author_id") as u64,
+                )
+            })
+            .collect();
+
+        Ok(messages)
+    }
+
+    async fn get_random_message(
+        &self,
+        guild_id: u64,
+        min_letters_amount: u64,
+        prefix_list: &[&str],
+        include_bots: bool,
+        channel_ids: Option<&[u64]>,
+    ) -> Result<Option<(String, u64)>, sqlx::Error> {
+        const BATCH_SIZE: usize = 20;
+
+        let channel_ids: Option<Vec<i64>> =
+            channel_ids.map(|ids| ids.iter().map(|&id| id as i64).collect());
+        let channel_ids = channel_ids.as_deref();
+
+        let range = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
+            "SELECT MIN(guild_seq), MAX(guild_seq) FROM messages WHERE guild_id = $1",
+        )
+        .bind(guild_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let (lo, hi) = match range {
+            (Some(lo), Some(hi)) => (lo, hi),
+            _ => return Ok(None),
+        };
+
+        let filter = format!(
+            r#"
+            WHERE guild_id = $1
+                AND LENGTH(content) >= $2
+                AND author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out)
+                AND ($3 OR NOT is_bot)
+                AND {}
+                AND ($4::BIGINT[] IS NULL OR channel_id = ANY($4))
+                AND NOT EXISTS (SELECT 1 FROM UNNEST($5::TEXT[]) AS prefix WHERE starts_with(content, prefix))
+            "#,
+            flags_filter("$1", "NOT generate_from")
+        );
+        let filter = &filter;
+        let message = |row: &PgRow| {
+            (
+                row.get::<String, _>("content"),
+                row.get::<i64, _>("author_id") as u64,
+            )
+        };
+
+        sampling::pick(
+            lo,
+            hi,
+            BATCH_SIZE,
+            |positions| async move {
+                let rows = sqlx::query(&format!(
+                    "SELECT content, author_id FROM messages {filter} AND guild_seq = ANY($6)"
+                ))
+                .bind(guild_id as i64)
+                .bind(min_letters_amount as i64)
+                .bind(include_bots)
+                .bind(channel_ids)
+                .bind(prefix_list)
+                .bind(positions)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(rows.iter().map(message).collect())
+            },
+            // Too few rows of the guild match, look only through the allowed channels
+            || async move {
+                let Some(channel_ids) = channel_ids else {
+                    return Ok(None);
+                };
+
+                let ranges = sqlx::query_as::<_, (i64, Option<i64>, Option<i64>)>(
+                    r#"
+                    SELECT channels.id,
+                        (SELECT MIN(channel_seq) FROM messages WHERE guild_id = $1 AND channel_id = channels.id),
+                        (SELECT MAX(channel_seq) FROM messages WHERE guild_id = $1 AND channel_id = channels.id)
+                    FROM UNNEST($2::BIGINT[]) AS channels (id)
+                    "#,
+                )
+                .bind(guild_id as i64)
+                .bind(channel_ids)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .filter_map(|(channel_id, lo, hi)| Some((channel_id as u64, lo?, hi?)))
+                .collect::<Vec<_>>();
+
+                let ranges = &ranges;
+                let found = sampling::sample(
+                    0,
+                    sampling::span(ranges) - 1,
+                    1,
+                    BATCH_SIZE,
+                    |positions| async move {
+                        let (channels, seqs): (Vec<i64>, Vec<i64>) = positions
+                            .into_iter()
+                            .filter_map(|position| sampling::locate(ranges, position))
+                            .map(|(channel_id, seq)| (channel_id as i64, seq))
+                            .unzip();
+
+                        let rows = sqlx::query(&format!(
+                            "SELECT content, author_id FROM messages {filter} AND (channel_id, channel_seq) IN (SELECT * FROM UNNEST($6::BIGINT[], $7::BIGINT[]))"
+                        ))
+                        .bind(guild_id as i64)
+                        .bind(min_letters_amount as i64)
+                        .bind(include_bots)
+                        .bind(Some(channel_ids))
+                        .bind(prefix_list)
+                        .bind(channels)
+                        .bind(seqs)
+                        .fetch_all(&self.pool)
+                        .await?;
+
+                        Ok(rows.iter().map(message).collect())
+                    },
+                )
+                .await?;
+
+                Ok(found.into_iter().next())
+            },
+        )
+        .await
+    }
+
+    async fn get_user_rating(&self, user_id: u64) -> Result<Option<f32>, sqlx::Error> {
+        let row = sqlx::query("SELECT rating FROM game_ratings WHERE user_id = $1")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(row.get::<f32, _>("rating"))),
+            None => Ok(None),
+        }
+    }
+
+    async fn insert_initial_rating(&self, user_id: u64, rating: f32) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO game_ratings (user_id, rating) VALUES ($1, $2)")
+            .bind(user_id.to_string())
+            .bind(rating)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_user_rating(&self, user_id: u64, rating: f32) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE game_ratings SET rating = $1 WHERE user_id = $2")
+            .bind(rating)
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_opted_out(&self, user_id: u64) -> Result<bool, sqlx::Error> {
+        let opted_out =
+            sqlx::query_scalar::<_, bool>("SELECT opted_out FROM user_consent WHERE user_id = $1")
+                .bind(user_id as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(opted_out.unwrap_or(false))
+    }
+
+    async fn set_opted_out(&self, user_id: u64, opted_out: bool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO user_consent (user_id, opted_out) VALUES ($1, $2) ON CONFLICT (user_id) DO UPDATE SET opted_out = excluded.opted_out",
+        )
+        .bind(user_id as i64)
+        .bind(opted_out)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn forget_user(&self, user_id: u64) -> Result<ForgottenUser, sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        // Waits for batches with the user's messages that are being written,
+        // see `insert_messages`
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(user_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        let messages = sqlx::query("DELETE FROM messages WHERE author_id = $1")
+            .bind(user_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        let reactions = sqlx::query("DELETE FROM generation_reactions WHERE user_id = $1")
+            .bind(user_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        let ratings = sqlx::query("DELETE FROM game_ratings WHERE user_id = $1")
+            .bind(user_id.to_string())
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        sqlx::query(
+            "INSERT INTO user_consent (user_id, opted_out) VALUES ($1, TRUE) ON CONFLICT (user_id) DO UPDATE SET opted_out = TRUE",
+        )
+        .bind(user_id as i64)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(ForgottenUser {
+            messages,
+            reactions,
+            ratings,
+        })
+    }
+
+    async fn get_guild_settings(
+        &self,
+        guild_id: u64,
+    ) -> Result<HashMap<String, String>, sqlx::Error> {
+        let rows = sqlx::query("SELECT key, value FROM guild_settings WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("key"), row.get("value")))
+            .collect())
+    }
+
+    async fn set_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+        value: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO guild_settings (guild_id, key, value) VALUES ($1, $2, $3) ON CONFLICT (guild_id, key) DO UPDATE SET value = excluded.value",
+        )
+        .bind(guild_id as i64)
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset_guild_settings(
+        &self,
+        guild_id: u64,
+        key: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "DELETE FROM guild_settings WHERE guild_id = $1 AND ($2::TEXT IS NULL OR key = $2)",
+        )
+        .bind(guild_id as i64)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        parent_id: Option<u64>,
+    ) -> Result<ChannelFlags, sqlx::Error> {
+        // The channel's own row wins over its parent's
+        let row = sqlx::query(
+            "SELECT collect, generate_from, post_into FROM channel_flags WHERE guild_id = $1 AND channel_id IN ($2, $3) ORDER BY channel_id != $2 LIMIT 1",
+        )
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .bind(parent_id.unwrap_or(channel_id) as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| channel_flags(&row)).unwrap_or_default())
+    }
+
+    async fn get_guild_channel_flags(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<(u64, ChannelFlags)>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT channel_id, collect, generate_from, post_into FROM channel_flags WHERE guild_id = $1 ORDER BY channel_id",
+        )
+        .bind(guild_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get::<i64, _>("channel_id") as u64, channel_flags(row)))
+            .collect())
+    }
+
+    async fn set_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        flags: ChannelFlags,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO channel_flags (guild_id, channel_id, collect, generate_from, post_into) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (guild_id, channel_id) DO UPDATE SET collect = excluded.collect, generate_from = excluded.generate_from, post_into = excluded.post_into",
+        )
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .bind(flags.collect)
+        .bind(flags.generate_from)
+        .bind(flags.post_into)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let deleted =
+            sqlx::query("DELETE FROM channel_flags WHERE guild_id = $1 AND channel_id = $2")
+                .bind(guild_id as i64)
+                .bind(channel_id as i64)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_command_rule(
+        &self,
+        guild_id: u64,
+        command: &str,
+    ) -> Result<Option<CommandRule>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT role_ids, permissions, user_cooldown_seconds, channel_cooldown_seconds FROM command_rules WHERE guild_id = $1 AND command = $2",
+        )
+        .bind(guild_id as i64)
+        .bind(command)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| command_rule(&row)))
+    }
+
+    async fn get_command_rules(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<(String, CommandRule)>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT command, role_ids, permissions, user_cooldown_seconds, channel_cooldown_seconds FROM command_rules WHERE guild_id = $1 ORDER BY command",
+        )
+        .bind(guild_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get::<String, _>("command"), command_rule(row)))
+            .collect())
+    }
+
+    async fn set_command_rule(
+        &self,
+        guild_id: u64,
+        command: &str,
+        rule: &CommandRule,
+    ) -> Result<(), sqlx::Error> {
+        let role_ids = rule
+            .role_ids
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        sqlx::query(
+            "INSERT INTO command_rules (guild_id, command, role_ids, permissions, user_cooldown_seconds, channel_cooldown_seconds) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (guild_id, command) DO UPDATE SET role_ids = excluded.role_ids, permissions = excluded.permissions, user_cooldown_seconds = excluded.user_cooldown_seconds, channel_cooldown_seconds = excluded.channel_cooldown_seconds",
+        )
+        .bind(guild_id as i64)
+        .bind(command)
+        .bind(role_ids)
+        .bind(rule.permissions as i64)
+        .bind(rule.user_cooldown_seconds as i64)
+        .bind(rule.channel_cooldown_seconds as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset_command_rule(&self, guild_id: u64, command: &str) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM command_rules WHERE guild_id = $1 AND command = $2")
+            .bind(guild_id as i64)
+            .bind(command)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_configured_guilds(&self) -> Result<Vec<u64>, sqlx::Error> {
+        let guild_ids =
+            sqlx::query_scalar::<_, i64>("SELECT DISTINCT guild_id FROM guild_settings")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(guild_ids.into_iter().map(|id| id as u64).collect())
+    }
+
+    async fn prune_messages_before(&self, guild_id: u64, before: u64) -> Result<u64, sqlx::Error> {
+        let mut pruned = 0;
+
+        // Delete in batches so the write lock is released in between
+        loop {
+            let deleted = sqlx::query(
+                "DELETE FROM messages WHERE id IN (SELECT id FROM messages WHERE guild_id = $1 AND message_id < $2 LIMIT $3)",
+            )
+            .bind(guild_id as i64)
+            .bind(before as i64)
+            .bind(PRUNE_BATCH_SIZE)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+            pruned += deleted;
+            if deleted < PRUNE_BATCH_SIZE as u64 {
+                return Ok(pruned);
+            }
+        }
+    }
+
+    async fn prune_channel_overflow(
+        &self,
+        guild_id: u64,
+        max_rows: u64,
+    ) -> Result<u64, sqlx::Error> {
+        let channel_ids = sqlx::query_scalar::<_, i64>(
+            "SELECT DISTINCT channel_id FROM messages WHERE guild_id = $1",
+        )
+        .bind(guild_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut pruned = 0;
+
+        for channel_id in channel_ids {
+            // The oldest message that is still kept
+            let cutoff = sqlx::query_scalar::<_, i64>(
+                "SELECT id FROM messages WHERE guild_id = $1 AND channel_id = $2 ORDER BY id DESC LIMIT 1 OFFSET $3",
+            )
+            .bind(guild_id as i64)
+            .bind(channel_id)
+            .bind(max_rows.saturating_sub(1) as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            let Some(cutoff) = cutoff else {
+                continue;
+            };
+
+            loop {
+                let deleted = sqlx::query(
+                    "DELETE FROM messages WHERE id IN (SELECT id FROM messages WHERE guild_id = $1 AND channel_id = $2 AND id < $3 LIMIT $4)",
+                )
+                .bind(guild_id as i64)
+                .bind(channel_id)
+                .bind(cutoff)
+                .bind(PRUNE_BATCH_SIZE)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+                pruned += deleted;
+                if deleted < PRUNE_BATCH_SIZE as u64 {
+                    break;
+                }
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    async fn optimize(&self, vacuum: bool) -> Result<(), sqlx::Error> {
+        if vacuum {
+            sqlx::query("VACUUM").execute(&self.pool).await?;
+        }
+
+        sqlx::query("ANALYZE").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn backup(&self, _destination: &Path) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "backups are only supported for SQLite, use pg_dump for PostgreSQL".into(),
+        ))
+    }
+
+    async fn flag_bot_author(&self, author_id: u64) -> Result<u64, sqlx::Error> {
+        let flagged =
+            sqlx::query("UPDATE messages SET is_bot = TRUE WHERE author_id = $1 AND NOT is_bot")
+                .bind(author_id as i64)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        Ok(flagged)
+    }
+
+    async fn schedule_guild_purge(
+        &self,
+        guild_id: u64,
+        purge_after: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO pending_guild_purges (guild_id, purge_after) VALUES ($1, $2) ON CONFLICT (guild_id) DO UPDATE SET purge_after = excluded.purge_after",
+        )
+        .bind(guild_id as i64)
+        .bind(purge_after)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn cancel_guild_purge(&self, guild_id: u64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM pending_guild_purges WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_pending_guild_purges(&self) -> Result<Vec<(u64, i64)>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT guild_id, purge_after FROM pending_guild_purges ORDER BY purge_after",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(guild_id, purge_after)| (guild_id as u64, purge_after))
+            .collect())
+    }
+
+    async fn get_stored_guilds(&self) -> Result<Vec<(u64, u64)>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT guild_id, COUNT(*) FROM messages GROUP BY guild_id ORDER BY guild_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(guild_id, count)| (guild_id as u64, count as u64))
+            .collect())
+    }
+
+    async fn purge_guild(&self, guild_id: u64) -> Result<u64, sqlx::Error> {
+        // All at once, so a failure leaves the guild whole and still pending
+        let mut transaction = self.pool.begin().await?;
+
+        let purged = sqlx::query("DELETE FROM messages WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        sqlx::query(
+            "DELETE FROM generation_reactions WHERE message_id IN (SELECT message_id FROM generations WHERE guild_id = $1)",
+        )
+        .bind(guild_id as i64)
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query("DELETE FROM generations WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM channel_flags WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM command_rules WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM guild_seq_locks WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM guild_settings WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM pending_guild_purges WHERE guild_id = $1")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(purged)
+    }
+
+    async fn log_generation(&self, generation: &Generation) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO generations (message_id, guild_id, channel_id, source_channel_id, content, seed, settings, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (message_id) DO UPDATE SET content = excluded.content, seed = excluded.seed, settings = excluded.settings",
+        )
+        .bind(generation.message_id as i64)
+        .bind(generation.guild_id as i64)
+        .bind(generation.channel_id as i64)
+        .bind(generation.source_channel_id as i64)
+        .bind(&generation.content)
+        .bind(&generation.seed)
+        .bind(&generation.settings)
+        .bind(generation.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_generation(&self, message_id: u64) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM generation_reactions WHERE message_id = $1")
+            .bind(message_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM generations WHERE message_id = $1")
+            .bind(message_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await
+    }
+
+    async fn add_generation_reaction(
+        &self,
+        message_id: u64,
+        user_id: u64,
+        emoji: &str,
+        value: i64,
+    ) -> Result<Option<Generation>, sqlx::Error> {
+        let is_generation = sqlx::query("SELECT 1 FROM generations WHERE message_id = $1")
+            .bind(message_id as i64)
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+
+        if !is_generation {
+            return Ok(None);
+        }
+
+        sqlx::query(
+            "INSERT INTO generation_reactions (message_id, user_id, emoji, value) VALUES ($1, $2, $3, $4) ON CONFLICT (message_id, user_id, emoji) DO NOTHING",
+        )
+        .bind(message_id as i64)
+        .bind(user_id as i64)
+        .bind(emoji)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+
+        let row = sqlx::query(
+            "SELECT generations.*, COALESCE((SELECT SUM(vote) FROM generation_votes WHERE generation_votes.message_id = generations.message_id), 0)::BIGINT AS score FROM generations WHERE message_id = $1",
+        )
+        .bind(message_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Some(generation(&row)))
+    }
+
+    async fn remove_generation_reactions(
+        &self,
+        message_id: u64,
+        user_id: Option<u64>,
+        emoji: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let user_id = user_id.map(|user_id| user_id as i64);
+
+        sqlx::query(
+            "DELETE FROM generation_reactions WHERE message_id = $1 AND ($2::BIGINT IS NULL OR user_id = $2) AND ($3::TEXT IS NULL OR emoji = $3)",
+        )
+        .bind(message_id as i64)
+        .bind(user_id)
+        .bind(emoji)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_generation_feedback(
+        &self,
+        guild_id: u64,
+        limit: u64,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, i64)>(
+            "SELECT generations.content, SUM(generation_votes.vote)::BIGINT AS score FROM generations JOIN generation_votes ON generation_votes.message_id = generations.message_id WHERE generations.guild_id = $1 GROUP BY generations.message_id HAVING SUM(generation_votes.vote) != 0 ORDER BY generations.created_at DESC LIMIT $2",
+        )
+        .bind(guild_id as i64)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn get_ranked_generations(
+        &self,
+        guild_id: u64,
+        best: bool,
+        limit: u64,
+    ) -> Result<Vec<Generation>, sqlx::Error> {
+        let query = if best {
+            "SELECT generations.*, SUM(generation_votes.vote)::BIGINT AS score FROM generations JOIN generation_votes ON generation_votes.message_id = generations.message_id WHERE generations.guild_id = $1 GROUP BY generations.message_id HAVING SUM(generation_votes.vote) > 0 ORDER BY score DESC, generations.created_at DESC LIMIT $2"
+        } else {
+            "SELECT generations.*, SUM(generation_votes.vote)::BIGINT AS score FROM generations JOIN generation_votes ON generation_votes.message_id = generations.message_id WHERE generations.guild_id = $1 GROUP BY generations.message_id HAVING SUM(generation_votes.vote) < 0 ORDER BY score ASC, generations.created_at DESC LIMIT $2"
+        };
+
+        let rows = sqlx::query(query)
+            .bind(guild_id as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(generation).collect())
+    }
+
+    async fn feature_generation(
+        &self,
+        message_id: u64,
+        featured_at: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let featured = sqlx::query(
+            "UPDATE generations SET featured_at = $1 WHERE message_id = $2 AND featured_at IS NULL",
+        )
+        .bind(featured_at)
+        .bind(message_id as i64)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(featured > 0)
+    }
+
+    async fn unfeature_generation(&self, message_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE generations SET featured_at = NULL WHERE message_id = $1")
+            .bind(message_id as i64)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_featured_generations(
+        &self,
+        guild_id: u64,
+        limit: u64,
+        offset: u64,
+    ) -> Result<(u64, Vec<Generation>), sqlx::Error> {
+        let total = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM generations WHERE guild_id = $1 AND featured_at IS NOT NULL",
+        )
+        .bind(guild_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let rows = sqlx::query(
+            "SELECT generations.*, COALESCE((SELECT SUM(vote) FROM generation_votes WHERE generation_votes.message_id = generations.message_id), 0)::BIGINT AS score FROM generations WHERE guild_id = $1 AND featured_at IS NOT NULL ORDER BY featured_at DESC, message_id DESC LIMIT $2 OFFSET $3",
+        )
+        .bind(guild_id as i64)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok((total as u64, rows.iter().map(generation).collect()))
+    }
+}
@@ -0,0 +1,1709 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use futures::stream::{BoxStream, StreamExt};
+use serenity::async_trait;
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow, SqliteSynchronous,
+};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool as Pool};
+
+use super::{
+    ChannelFlags, CommandRule, ForgottenUser, Generation, MessageFilter, Storage, StoredMessage,
+};
+use crate::utils::sampling;
+
+/// Maximum amount of rows deleted per statement while pruning.
+const PRUNE_BATCH_SIZE: i64 = 5000;
+
+pub struct SqliteDatabase {
+    pool: Pool,
+}
+
+impl SqliteDatabase {
+    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
+        // WAL lets the generation and leaderboard reads run while the message
+        // queue is writing, NORMAL sync is durable enough in WAL mode
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(Duration::from_secs(5));
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(8)
+            .connect_with(options)
+            .await?;
+
+        // Create tables if they don't exist
+        Self::setup_tables(&pool).await?;
+
+        Ok(SqliteDatabase { pool })
+    }
+
+    async fn setup_tables(pool: &Pool) -> Result<(), sqlx::Error> {
+        // Create messages table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                message_id INTEGER NOT NULL,
+                author_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                guild_id INTEGER NOT NULL,
+                content TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Dense per-channel and per-guild ordinals, used to sample random messages
+        // with indexed lookups instead of OFFSET scans
+        let added_channel_seq =
+            Self::add_column_if_missing(pool, "messages", "channel_seq", "INTEGER").await?;
+        let added_guild_seq =
+            Self::add_column_if_missing(pool, "messages", "guild_seq", "INTEGER").await?;
+
+        // Bot and webhook messages, kept out of generation unless a guild opts in
+        Self::add_column_if_missing(pool, "messages", "is_bot", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+
+        // The parent channel of messages sent in threads, whose flags they share
+        Self::add_column_if_missing(pool, "messages", "parent_id", "INTEGER").await?;
+
+        if added_channel_seq || added_guild_seq {
+            sqlx::query(
+                r#"
+                UPDATE messages SET channel_seq = ranked.channel_seq, guild_seq = ranked.guild_seq
+                FROM (
+                    SELECT id,
+                        ROW_NUMBER() OVER (PARTITION BY guild_id, channel_id ORDER BY id) - 1 AS channel_seq,
+                        ROW_NUMBER() OVER (PARTITION BY guild_id ORDER BY id) - 1 AS guild_seq
+                    FROM messages
+                ) AS ranked
+                WHERE messages.id = ranked.id
+                "#,
+            )
+            .execute(pool)
+            .await?;
+        }
+
+        // Create indexes for performance
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_guild_channel ON messages (guild_id, channel_id)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_guild_author ON messages (guild_id, author_id)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_guild ON messages (guild_id)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_channel_seq ON messages (guild_id, channel_id, channel_seq)")
+            .execute(pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_messages_guild_seq ON messages (guild_id, guild_seq)",
+        )
+        .execute(pool)
+        .await?;
+
+        // Create game_ratings table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS game_ratings (
+                user_id TEXT PRIMARY KEY,
+                rating REAL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create the full-text index over message contents. It is an external
+        // content table, so it only stores the index and reads rows from `messages`
+        let fts_exists = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'messages_fts'",
+        )
+        .fetch_one(pool)
+        .await?
+            > 0;
+
+        sqlx::query(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(content, content = 'messages', content_rowid = 'id')",
+        )
+        .execute(pool)
+        .await?;
+
+        // Keep the index in sync with the messages table
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+                INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+            END
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+                INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+            END
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+                INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.id, old.content);
+                INSERT INTO messages_fts (rowid, content) VALUES (new.id, new.content);
+            END
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Index the messages stored before the index existed
+        if !fts_exists {
+            sqlx::query("INSERT INTO messages_fts (messages_fts) VALUES ('rebuild')")
+                .execute(pool)
+                .await?;
+        }
+
+        // Create guild_settings table, holds only the values changed from their defaults
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS guild_settings (
+                guild_id INTEGER NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (guild_id, key)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // The autopost wait used to be a range, carry it over as the interval
+        // in its middle give or take half its width (the old defaults, 300 and
+        // 900, are the new ones). Settings already in the new form are kept
+        sqlx::query(
+            r#"
+            WITH legacy AS (
+                SELECT guild_id, MIN(low, high) AS low, MAX(low, high) AS high
+                FROM (
+                    SELECT guild_id,
+                        COALESCE(MAX(CASE WHEN key = 'autopost_min_interval_seconds' THEN CAST(value AS INTEGER) END), 300) AS low,
+                        COALESCE(MAX(CASE WHEN key = 'autopost_max_interval_seconds' THEN CAST(value AS INTEGER) END), 900) AS high
+                    FROM guild_settings
+                    WHERE key IN ('autopost_min_interval_seconds', 'autopost_max_interval_seconds')
+                    GROUP BY guild_id
+                )
+            )
+            INSERT OR IGNORE INTO guild_settings (guild_id, key, value)
+            SELECT guild_id, 'autopost_interval_seconds', CAST((low + high) / 2 AS TEXT) FROM legacy
+            UNION ALL
+            SELECT guild_id, 'autopost_jitter_seconds', CAST((high - low) / 2 AS TEXT) FROM legacy
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query(
+            "DELETE FROM guild_settings WHERE key IN ('autopost_min_interval_seconds', 'autopost_max_interval_seconds')",
+        )
+        .execute(pool)
+        .await?;
+
+        // Create channel_flags table, channels without a row allow everything
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS channel_flags (
+                guild_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                collect INTEGER NOT NULL,
+                generate_from INTEGER NOT NULL,
+                post_into INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, channel_id)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create command_rules table, commands without a row are open to everyone
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS command_rules (
+                guild_id INTEGER NOT NULL,
+                command TEXT NOT NULL,
+                role_ids TEXT NOT NULL,
+                permissions INTEGER NOT NULL,
+                user_cooldown_seconds INTEGER NOT NULL,
+                channel_cooldown_seconds INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, command)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create pending_guild_purges table, for guilds the bot was removed from
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS pending_guild_purges (
+                guild_id INTEGER PRIMARY KEY,
+                purge_after INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create user_consent table, users listed here have opted out of collection
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_consent (
+                user_id INTEGER PRIMARY KEY,
+                opted_out INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // Create generations table, every message the bot generated and posted
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS generations (
+                message_id INTEGER PRIMARY KEY,
+                guild_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                source_channel_id INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                seed TEXT,
+                settings TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_generations_guild ON generations (guild_id, created_at)")
+            .execute(pool)
+            .await?;
+
+        // When a generation was reposted to the hall of fame
+        Self::add_column_if_missing(pool, "generations", "featured_at", "INTEGER").await?;
+
+        // Create generation_reactions table, the feedback members gave on generations
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS generation_reactions (
+                message_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                emoji TEXT NOT NULL,
+                value INTEGER NOT NULL,
+                PRIMARY KEY (message_id, user_id, emoji)
+            )
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        // One vote per member and generation, however many emoji they reacted with
+        sqlx::query(
+            r#"
+            CREATE VIEW IF NOT EXISTS generation_votes AS
+            SELECT message_id, user_id, MAX(-1, MIN(1, SUM(value))) AS vote
+            FROM generation_reactions
+            GROUP BY message_id, user_id
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Adds a column to an existing table, returning whether it was missing.
+    async fn add_column_if_missing(
+        pool: &Pool,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?",
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_one(pool)
+        .await?
+            > 0;
+
+        if exists {
+            return Ok(false);
+        }
+
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .execute(pool)
+        .await?;
+
+        Ok(true)
+    }
+}
+
+/// Restricts a query to the given channels, matching nothing if there are none.
+fn push_channel_filter(query: &mut QueryBuilder<'_, Sqlite>, channel_ids: &[u64]) {
+    if channel_ids.is_empty() {
+        query.push(" AND 0");
+        return;
+    }
+
+    query.push(" AND channel_id IN (");
+    let mut separated = query.separated(", ");
+    for channel_id in channel_ids {
+        separated.push_bind(*channel_id as i64);
+    }
+    separated.push_unseparated(")");
+}
+
+/// Returns the `channel_seq` range of each of the channels that has messages.
+async fn channel_seq_ranges(
+    pool: &Pool,
+    guild_id: u64,
+    channel_ids: &[u64],
+) -> Result<Vec<(u64, i64, i64)>, sqlx::Error> {
+    if channel_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Separate lookups per bound, so each is a single index seek
+    let mut query = QueryBuilder::<Sqlite>::new("WITH channels(id) AS (VALUES ");
+    let mut separated = query.separated(", ");
+    for &channel_id in channel_ids {
+        separated
+            .push("(")
+            .push_bind_unseparated(channel_id as i64)
+            .push_unseparated(")");
+    }
+    query
+        .push(") SELECT id, (SELECT MIN(channel_seq) FROM messages WHERE guild_id = ")
+        .push_bind(guild_id as i64)
+        .push(" AND channel_id = channels.id), (SELECT MAX(channel_seq) FROM messages WHERE guild_id = ")
+        .push_bind(guild_id as i64)
+        .push(" AND channel_id = channels.id) FROM channels");
+
+    let rows = query
+        .build_query_as::<(i64, Option<i64>, Option<i64>)>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(channel_id, lo, hi)| Some((channel_id as u64, lo?, hi?)))
+        .collect())
+}
+
+/// Leaves out the messages of channels whose flags match `excluded`, such as
+/// `generate_from = 0`. Threads without flags of their own take those of
+/// their parent channel, as in `get_channel_flags`.
+fn push_flags_filter(query: &mut QueryBuilder<'_, Sqlite>, guild_id: u64, excluded: &str) {
+    query
+        .push(" AND messages.channel_id NOT IN (SELECT channel_id FROM channel_flags WHERE guild_id = ")
+        .push_bind(guild_id as i64)
+        .push(" AND ")
+        .push(excluded)
+        .push(") AND (messages.parent_id IS NULL OR messages.parent_id NOT IN (SELECT channel_id FROM channel_flags WHERE guild_id = ")
+        .push_bind(guild_id as i64)
+        .push(" AND ")
+        .push(excluded)
+        .push(") OR messages.channel_id IN (SELECT channel_id FROM channel_flags WHERE guild_id = ")
+        .push_bind(guild_id as i64)
+        .push("))");
+}
+
+fn channel_flags(row: &SqliteRow) -> ChannelFlags {
+    ChannelFlags {
+        collect: row.get("collect"),
+        generate_from: row.get("generate_from"),
+        post_into: row.get("post_into"),
+    }
+}
+
+fn command_rule(row: &SqliteRow) -> CommandRule {
+    CommandRule {
+        role_ids: row
+            .get::<String, _>("role_ids")
+            .split_whitespace()
+            .filter_map(|role_id| role_id.parse().ok())
+            .collect(),
+        permissions: row.get::<i64, _>("permissions") as u64,
+        user_cooldown_seconds: row.get::<i64, _>("user_cooldown_seconds") as u64,
+        channel_cooldown_seconds: row.get::<i64, _>("channel_cooldown_seconds") as u64,
+    }
+}
+
+fn generation(row: &SqliteRow) -> Generation {
+    Generation {
+        message_id: row.get::<i64, _>("message_id") as u64,
+        guild_id: row.get::<i64, _>("guild_id") as u64,
+        channel_id: row.get::<i64, _>("channel_id") as u64,
+        source_channel_id: row.get::<i64, _>("source_channel_id") as u64,
+        content: row.get("content"),
+        seed: row.get("seed"),
+        settings: row.get("settings"),
+        created_at: row.get("created_at"),
+        score: row.get("score"),
+        featured_at: row.get("featured_at"),
+    }
+}
+
+fn stored_message(row: &SqliteRow) -> StoredMessage {
+    StoredMessage {
+        message_id: row.get::<i64, _>("message_id") as u64,
+        author_id: row.get::<i64, _>("author_id") as u64,
+        channel_id: row.get::<i64, _>("channel_id") as u64,
+        guild_id: row.get::<i64, _>("guild_id") as u64,
+        content: row.get("content"),
+        is_bot: row.get::<i64, _>("is_bot") != 0,
+        parent_id: row.get::<Option<i64>, _>("parent_id").map(|id| id as u64),
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteDatabase {
+    async fn insert_messages(&self, messages: &[StoredMessage]) -> Result<(), sqlx::Error> {
+        // Writes are serialized, so a `forget_user` either sees these rows or
+        // has already opted their author out
+        let mut transaction = self.pool.begin().await?;
+
+        for message in messages {
+            sqlx::query(
+                r#"
+                INSERT INTO messages (message_id, author_id, channel_id, guild_id, content, is_bot, parent_id, channel_seq, guild_seq)
+                SELECT ?, ?, ?, ?, ?, ?, ?,
+                    COALESCE((SELECT MAX(channel_seq) FROM messages WHERE guild_id = ? AND channel_id = ?), -1) + 1,
+                    COALESCE((SELECT MAX(guild_seq) FROM messages WHERE guild_id = ?), -1) + 1
+                WHERE NOT EXISTS (SELECT 1 FROM user_consent WHERE user_id = ? AND opted_out = 1)
+                "#,
+            )
+            .bind(message.message_id as i64)
+            .bind(message.author_id as i64)
+            .bind(message.channel_id as i64)
+            .bind(message.guild_id as i64)
+            .bind(&message.content)
+            .bind(message.is_bot as i64)
+            .bind(message.parent_id.map(|id| id as i64))
+            .bind(message.guild_id as i64)
+            .bind(message.channel_id as i64)
+            .bind(message.guild_id as i64)
+            .bind(message.author_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_messages_for_markov(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        blacklist_prefixes: &[&str],
+        include_bots: bool,
+        limit: usize,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let is_allowed = move |content: &String| {
+            !blacklist_prefixes
+                .iter()
+                .any(|&prefix| content.starts_with(prefix))
+        };
+
+        let range = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
+            "SELECT MIN(channel_seq), MAX(channel_seq) FROM messages WHERE guild_id = ? AND channel_id = ?",
+        )
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let (lo, hi) = match range {
+            (Some(lo), Some(hi)) => (lo, hi),
+            _ => return Ok(Vec::new()),
+        };
+
+        sampling::sample(lo, hi, limit, 0, |positions| async move {
+            let mut query =
+                QueryBuilder::<Sqlite>::new("SELECT content FROM messages WHERE guild_id = ");
+            query
+                .push_bind(guild_id as i64)
+                .push(" AND channel_id = ")
+                .push_bind(channel_id as i64)
+                .push(" AND LENGTH(content) > 10 AND author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out = 1)");
+            if !include_bots {
+                query.push(" AND is_bot = 0");
+            }
+            push_flags_filter(&mut query, guild_id, "generate_from = 0");
+            query.push(" AND channel_seq IN (");
+            let mut separated = query.separated(", ");
+            for position in positions {
+                separated.push_bind(position);
+            }
+            separated.push_unseparated(")");
+
+            let rows = query.build().fetch_all(&self.pool).await?;
+            Ok(rows
+                .iter()
+                .map(|row| row.get::<String, _>("content"))
+                .filter(is_allowed)
+                .collect())
+        })
+        .await
+    }
+
+    async fn get_author_messages_for_markov(
+        &self,
+        guild_id: u64,
+        author_id: u64,
+        blacklist_prefixes: &[&str],
+        include_bots: bool,
+        channel_ids: Option<&[u64]>,
+        limit: usize,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        // A member's messages are scattered over the guild's ordinals, so find
+        // theirs first and only fetch the contents of the sampled ones
+        let mut query =
+            QueryBuilder::<Sqlite>::new("SELECT guild_seq FROM messages WHERE guild_id = ");
+        query
+            .push_bind(guild_id as i64)
+            .push(" AND author_id = ")
+            .push_bind(author_id as i64)
+            .push(" AND LENGTH(content) > 10 AND author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out = 1)");
+        if !include_bots {
+            query.push(" AND is_bot = 0");
+        }
+        push_flags_filter(&mut query, guild_id, "generate_from = 0");
+        if let Some(channel_ids) = channel_ids {
+            push_channel_filter(&mut query, channel_ids);
+        }
+        let positions: Vec<i64> = query.build_query_scalar().fetch_all(&self.pool).await?;
+
+        sampling::sample_known(positions, limit, |positions| async move {
+            let mut query =
+                QueryBuilder::<Sqlite>::new("SELECT content FROM messages WHERE guild_id = ");
+            query.push_bind(guild_id as i64).push(" AND guild_seq IN (");
+            let mut separated = query.separated(", ");
+            for position in positions {
+                separated.push_bind(position);
+            }
+            separated.push_unseparated(")");
+
+            let rows = query.build().fetch_all(&self.pool).await?;
+            Ok(rows
+                .iter()
+                .map(|row| row.get::<String, _>("content"))
+                .filter(|content| {
+                    !blacklist_prefixes
+                        .iter()
+                        .any(|&prefix| content.starts_with(prefix))
+                })
+                .collect())
+        })
+        .await
+    }
+
+    fn stream_messages(
+        &self,
+        filter: &MessageFilter,
+    ) -> BoxStream<'_, Result<StoredMessage, sqlx::Error>> {
+        sqlx::query(
+            r#"
+            SELECT message_id, author_id, channel_id, guild_id, content, is_bot, parent_id FROM messages
+            WHERE guild_id = ?
+                AND (? IS NULL OR channel_id = ?)
+                AND (? IS NULL OR author_id = ?)
+                AND (? IS NULL OR message_id >= ?)
+                AND (? IS NULL OR message_id < ?)
+            ORDER BY id
+            "#,
+        )
+        .bind(filter.guild_id as i64)
+        .bind(filter.channel_id.map(|id| id as i64))
+        .bind(filter.channel_id.map(|id| id as i64))
+        .bind(filter.author_id.map(|id| id as i64))
+        .bind(filter.author_id.map(|id| id as i64))
+        .bind(filter.since.map(|id| id as i64))
+        .bind(filter.since.map(|id| id as i64))
+        .bind(filter.until.map(|id| id as i64))
+        .bind(filter.until.map(|id| id as i64))
+        .fetch(&self.pool)
+        .map(|row| row.map(|row| stored_message(&row)))
+        .boxed()
+    }
+
+    async fn search_messages(
+        &self,
+        guild_id: u64,
+        query: &str,
+        author_id: Option<u64>,
+        channel_ids: Option<&[u64]>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<(u64, Vec<StoredMessage>), sqlx::Error> {
+        // Quote the query so FTS5 operators in user input are matched literally
+        let phrase = format!("\"{}\"", query.replace('"', "\"\""));
+
+        let filter = |query: &mut QueryBuilder<'_, Sqlite>| {
+            query
+                .push(" WHERE messages_fts MATCH ")
+                .push_bind(phrase.clone())
+                .push(" AND messages.guild_id = ")
+                .push_bind(guild_id as i64);
+            if let Some(author_id) = author_id {
+                query
+                    .push(" AND messages.author_id = ")
+                    .push_bind(author_id as i64);
+            }
+            query.push(
+                " AND messages.author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out = 1)",
+            );
+            push_flags_filter(query, guild_id, "generate_from = 0");
+            if let Some(channel_ids) = channel_ids {
+                push_channel_filter(query, channel_ids);
+            }
+        };
+
+        let mut count = QueryBuilder::<Sqlite>::new(
+            "SELECT COUNT(*) FROM messages_fts JOIN messages ON messages.id = messages_fts.rowid",
+        );
+        filter(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.pool).await?;
+
+        let mut page = QueryBuilder::<Sqlite>::new(
+            "SELECT messages.message_id, messages.author_id, messages.channel_id, messages.guild_id, messages.content, messages.is_bot, messages.parent_id FROM messages_fts JOIN messages ON messages.id = messages_fts.rowid",
+        );
+        filter(&mut page);
+        page.push(" ORDER BY messages_fts.rank LIMIT ")
+            .push_bind(limit as i64)
+            .push(" OFFSET ")
+            .push_bind(offset as i64);
+        let rows = page.build().fetch_all(&self.pool).await?;
+
+        let messages = rows.iter().map(stored_message).collect();
+
+        Ok((total as u64, messages))
+    }
+
+    async fn get_most_popular_channel(
+        &self,
+        guild_id: u64,
+        channel_ids: Option<&[u64]>,
+    ) -> Result<u64, sqlx::Error> {
+        let mut query =
+            QueryBuilder::<Sqlite>::new("SELECT channel_id FROM messages WHERE guild_id = ");
+        query.push_bind(guild_id as i64);
+        push_flags_filter(&mut query, guild_id, "(generate_from = 0 OR post_into = 0)");
+        if let Some(channel_ids) = channel_ids {
+            push_channel_filter(&mut query, channel_ids);
+        }
+        query.push(" GROUP BY channel_id ORDER BY COUNT(*) DESC LIMIT 1");
+
+        let row = query.build().fetch_optional(&self.pool).await?;
+
+        match row {
+            Some(row) => Ok(row.get::<i64, _>("channel_id") as u64),
+            None => Ok(0),
+        }
+    }
+
+    async fn get_messages_for_leaderboard(
+        &self,
+        guild_id: u64,
+        member_id: Option<u64>,
+    ) -> Result<Vec<(String, u64)>, sqlx::Error> {
+        // Add LIMIT to prevent memory issues on large servers
+        const MAX_MESSAGES: i64 = 50000; // Reasonable limit for processing
+
+        let rows = if let Some(member_id) = member_id {
+            sqlx::query("SELECT content, author_id FROM messages WHERE guild_id = ? AND author_id = ? LIMIT ?")
+                .bind(guild_id as i64)
+                .bind(member_id as i64)
+                .bind(MAX_MESSAGES)
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            sqlx::query("SELECT content, author_id FROM messages WHERE guild_id = ? LIMIT ?")
+                .bind(guild_id as i64)
+                .bind(MAX_MESSAGES)
+                .fetch_all(&self.pool)
+                .await?
+        };
+
+        let messages: Vec<(String, u64)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<String, _>("content"),
+                    row.get::<i64, _>("author_id") as u64,
+                )
+            })
+            .collect();
+
+        Ok(messages)
+    }
+
+    async fn get_random_message(
+        &self,
+        guild_id: u64,
+        min_letters_amount: u64,
+        prefix_list: &[&str],
+        include_bots: bool,
+        channel_ids: Option<&[u64]>,
+    ) -> Result<Option<(String, u64)>, sqlx::Error> {
+        const BATCH_SIZE: usize = 20;
+
+        let range = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
+            "SELECT MIN(guild_seq), MAX(guild_seq) FROM messages WHERE guild_id = ?",
+        )
+        .bind(guild_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let (lo, hi) = match range {
+            (Some(lo), Some(hi)) => (lo, hi),
+            _ => return Ok(None),
+        };
+
+        let filter = |query: &mut QueryBuilder<'_, Sqlite>| {
+            query
+                .push(" WHERE guild_id = ")
+                .push_bind(guild_id as i64)
+                .push(" AND LENGTH(content) >= ")
+                .push_bind(min_letters_amount as i64)
+                .push(
+                    " AND author_id NOT IN (SELECT user_id FROM user_consent WHERE opted_out = 1)",
+                );
+            if !include_bots {
+                query.push(" AND is_bot = 0");
+            }
+            push_flags_filter(query, guild_id, "generate_from = 0");
+            if let Some(channel_ids) = channel_ids {
+                push_channel_filter(query, channel_ids);
+            }
+            for &prefix in prefix_list {
+                query
+                    .push(" AND SUBSTR(content, 1, LENGTH(")
+                    .push_bind(prefix.to_string())
+                    .push(")) != ")
+                    .push_bind(prefix.to_string());
+            }
+        };
+        let message = |row: &SqliteRow| {
+            (
+                row.get::<String, _>("content"),
+                row.get::<i64, _>("author_id") as u64,
+            )
+        };
+
+        sampling::pick(
+            lo,
+            hi,
+            BATCH_SIZE,
+            |positions| async move {
+                let mut query =
+                    QueryBuilder::<Sqlite>::new("SELECT content, author_id FROM messages");
+                filter(&mut query);
+                query.push(" AND guild_seq IN (");
+                let mut separated = query.separated(", ");
+                for position in positions {
+                    separated.push_bind(position);
+                }
+                separated.push_unseparated(")");
+
+                let rows = query.build().fetch_all(&self.pool).await?;
+
+                Ok(rows.iter().map(message).collect())
+            },
+            // Too few rows of the guild match, look only through the allowed channels
+            || async move {
+                let Some(channel_ids) = channel_ids else {
+                    return Ok(None);
+                };
+
+                let ranges = channel_seq_ranges(&self.pool, guild_id, channel_ids).await?;
+                let ranges = &ranges;
+                let found = sampling::sample(
+                    0,
+                    sampling::span(ranges) - 1,
+                    1,
+                    BATCH_SIZE,
+                    |positions| async move {
+                        let mut query =
+                            QueryBuilder::<Sqlite>::new("SELECT content, author_id FROM messages");
+                        filter(&mut query);
+                        query.push(" AND (channel_id, channel_seq) IN (VALUES ");
+                        let mut separated = query.separated(", ");
+                        for position in positions {
+                            if let Some((channel_id, seq)) = sampling::locate(ranges, position) {
+                                separated
+                                    .push("(")
+                                    .push_bind_unseparated(channel_id as i64)
+                                    .push_unseparated(", ")
+                                    .push_bind_unseparated(seq)
+                                    .push_unseparated(")");
+                            }
+                        }
+                        separated.push_unseparated(")");
+
+                        let rows = query.build().fetch_all(&self.pool).await?;
+
+                        Ok(rows.iter().map(message).collect())
+                    },
+                )
+                .await?;
+
+                Ok(found.into_iter().next())
+            },
+        )
+        .await
+    }
+
+    async fn get_user_rating(&self, user_id: u64) -> Result<Option<f32>, sqlx::Error> {
+        let row = sqlx::query("SELECT rating FROM game_ratings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(row.get::<f32, _>("rating"))),
+            None => Ok(None),
+        }
+    }
+
+    async fn insert_initial_rating(&self, user_id: u64, rating: f32) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO game_ratings (user_id, rating) VALUES (?, ?)")
+            .bind(user_id.to_string())
+            .bind(rating)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_user_rating(&self, user_id: u64, rating: f32) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE game_ratings SET rating = ? WHERE user_id = ?")
+            .bind(rating)
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_opted_out(&self, user_id: u64) -> Result<bool, sqlx::Error> {
+        let opted_out =
+            sqlx::query_scalar::<_, bool>("SELECT opted_out FROM user_consent WHERE user_id = ?")
+                .bind(user_id as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(opted_out.unwrap_or(false))
+    }
+
+    async fn set_opted_out(&self, user_id: u64, opted_out: bool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO user_consent (user_id, opted_out) VALUES (?, ?) ON CONFLICT(user_id) DO UPDATE SET opted_out = excluded.opted_out",
+        )
+        .bind(user_id as i64)
+        .bind(opted_out)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn forget_user(&self, user_id: u64) -> Result<ForgottenUser, sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        let messages = sqlx::query("DELETE FROM messages WHERE author_id = ?")
+            .bind(user_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        let reactions = sqlx::query("DELETE FROM generation_reactions WHERE user_id = ?")
+            .bind(user_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        let ratings = sqlx::query("DELETE FROM game_ratings WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        sqlx::query(
+            "INSERT INTO user_consent (user_id, opted_out) VALUES (?, 1) ON CONFLICT(user_id) DO UPDATE SET opted_out = 1",
+        )
+        .bind(user_id as i64)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(ForgottenUser {
+            messages,
+            reactions,
+            ratings,
+        })
+    }
+
+    async fn get_guild_settings(
+        &self,
+        guild_id: u64,
+    ) -> Result<HashMap<String, String>, sqlx::Error> {
+        let rows = sqlx::query("SELECT key, value FROM guild_settings WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("key"), row.get("value")))
+            .collect())
+    }
+
+    async fn set_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+        value: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO guild_settings (guild_id, key, value) VALUES (?, ?, ?) ON CONFLICT (guild_id, key) DO UPDATE SET value = excluded.value",
+        )
+        .bind(guild_id as i64)
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset_guild_settings(
+        &self,
+        guild_id: u64,
+        key: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM guild_settings WHERE guild_id = ? AND (? IS NULL OR key = ?)")
+            .bind(guild_id as i64)
+            .bind(key)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        parent_id: Option<u64>,
+    ) -> Result<ChannelFlags, sqlx::Error> {
+        // The channel's own row wins over its parent's
+        let row = sqlx::query(
+            "SELECT collect, generate_from, post_into FROM channel_flags WHERE guild_id = ? AND channel_id IN (?, ?) ORDER BY channel_id != ? LIMIT 1",
+        )
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .bind(parent_id.unwrap_or(channel_id) as i64)
+        .bind(channel_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| channel_flags(&row)).unwrap_or_default())
+    }
+
+    async fn get_guild_channel_flags(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<(u64, ChannelFlags)>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT channel_id, collect, generate_from, post_into FROM channel_flags WHERE guild_id = ? ORDER BY channel_id",
+        )
+        .bind(guild_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get::<i64, _>("channel_id") as u64, channel_flags(row)))
+            .collect())
+    }
+
+    async fn set_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        flags: ChannelFlags,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO channel_flags (guild_id, channel_id, collect, generate_from, post_into) VALUES (?, ?, ?, ?, ?) ON CONFLICT (guild_id, channel_id) DO UPDATE SET collect = excluded.collect, generate_from = excluded.generate_from, post_into = excluded.post_into",
+        )
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .bind(flags.collect)
+        .bind(flags.generate_from)
+        .bind(flags.post_into)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset_channel_flags(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        let deleted =
+            sqlx::query("DELETE FROM channel_flags WHERE guild_id = ? AND channel_id = ?")
+                .bind(guild_id as i64)
+                .bind(channel_id as i64)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_command_rule(
+        &self,
+        guild_id: u64,
+        command: &str,
+    ) -> Result<Option<CommandRule>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT role_ids, permissions, user_cooldown_seconds, channel_cooldown_seconds FROM command_rules WHERE guild_id = ? AND command = ?",
+        )
+        .bind(guild_id as i64)
+        .bind(command)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| command_rule(&row)))
+    }
+
+    async fn get_command_rules(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<(String, CommandRule)>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT command, role_ids, permissions, user_cooldown_seconds, channel_cooldown_seconds FROM command_rules WHERE guild_id = ? ORDER BY command",
+        )
+        .bind(guild_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get::<String, _>("command"), command_rule(row)))
+            .collect())
+    }
+
+    async fn set_command_rule(
+        &self,
+        guild_id: u64,
+        command: &str,
+        rule: &CommandRule,
+    ) -> Result<(), sqlx::Error> {
+        let role_ids = rule
+            .role_ids
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        sqlx::query(
+            "INSERT INTO command_rules (guild_id, command, role_ids, permissions, user_cooldown_seconds, channel_cooldown_seconds) VALUES (?, ?, ?, ?, ?, ?) ON CONFLICT (guild_id, command) DO UPDATE SET role_ids = excluded.role_ids, permissions = excluded.permissions, user_cooldown_seconds = excluded.user_cooldown_seconds, channel_cooldown_seconds = excluded.channel_cooldown_seconds",
+        )
+        .bind(guild_id as i64)
+        .bind(command)
+        .bind(role_ids)
+        .bind(rule.permissions as i64)
+        .bind(rule.user_cooldown_seconds as i64)
+        .bind(rule.channel_cooldown_seconds as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset_command_rule(&self, guild_id: u64, command: &str) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM command_rules WHERE guild_id = ? AND command = ?")
+            .bind(guild_id as i64)
+            .bind(command)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_configured_guilds(&self) -> Result<Vec<u64>, sqlx::Error> {
+        let guild_ids =
+            sqlx::query_scalar::<_, i64>("SELECT DISTINCT guild_id FROM guild_settings")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(guild_ids.into_iter().map(|id| id as u64).collect())
+    }
+
+    async fn prune_messages_before(&self, guild_id: u64, before: u64) -> Result<u64, sqlx::Error> {
+        let mut pruned = 0;
+
+        // Delete in batches so the write lock is released in between
+        loop {
+            let deleted = sqlx::query(
+                "DELETE FROM messages WHERE id IN (SELECT id FROM messages WHERE guild_id = ? AND message_id < ? LIMIT ?)",
+            )
+            .bind(guild_id as i64)
+            .bind(before as i64)
+            .bind(PRUNE_BATCH_SIZE)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+            pruned += deleted;
+            if deleted < PRUNE_BATCH_SIZE as u64 {
+                return Ok(pruned);
+            }
+        }
+    }
+
+    async fn prune_channel_overflow(
+        &self,
+        guild_id: u64,
+        max_rows: u64,
+    ) -> Result<u64, sqlx::Error> {
+        let channel_ids = sqlx::query_scalar::<_, i64>(
+            "SELECT DISTINCT channel_id FROM messages WHERE guild_id = ?",
+        )
+        .bind(guild_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut pruned = 0;
+
+        for channel_id in channel_ids {
+            // The oldest message that is still kept
+            let cutoff = sqlx::query_scalar::<_, i64>(
+                "SELECT id FROM messages WHERE guild_id = ? AND channel_id = ? ORDER BY id DESC LIMIT 1 OFFSET ?",
+            )
+            .bind(guild_id as i64)
+            .bind(channel_id)
+            .bind(max_rows.saturating_sub(1) as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            let Some(cutoff) = cutoff else {
+                continue;
+            };
+
+            loop {
+                let deleted = sqlx::query(
+                    "DELETE FROM messages WHERE id IN (SELECT id FROM messages WHERE guild_id = ? AND channel_id = ? AND id < ? LIMIT ?)",
+                )
+                .bind(guild_id as i64)
+                .bind(channel_id)
+                .bind(cutoff)
+                .bind(PRUNE_BATCH_SIZE)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+                pruned += deleted;
+                if deleted < PRUNE_BATCH_SIZE as u64 {
+                    break;
+                }
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    async fn optimize(&self, vacuum: bool) -> Result<(), sqlx::Error> {
+        if vacuum {
+            // Merge the full-text index segments before compacting the file
+            sqlx::query("INSERT INTO messages_fts (messages_fts) VALUES ('optimize')")
+                .execute(&self.pool)
+                .await?;
+            sqlx::query("VACUUM").execute(&self.pool).await?;
+        }
+
+        sqlx::query("ANALYZE").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn backup(&self, destination: &Path) -> Result<(), sqlx::Error> {
+        // Readers and writers keep going while the snapshot is taken thanks to WAL
+        sqlx::query("VACUUM INTO ?")
+            .bind(destination.to_string_lossy())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn flag_bot_author(&self, author_id: u64) -> Result<u64, sqlx::Error> {
+        let flagged =
+            sqlx::query("UPDATE messages SET is_bot = 1 WHERE author_id = ? AND is_bot = 0")
+                .bind(author_id as i64)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+
+        Ok(flagged)
+    }
+
+    async fn schedule_guild_purge(
+        &self,
+        guild_id: u64,
+        purge_after: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO pending_guild_purges (guild_id, purge_after) VALUES (?, ?) ON CONFLICT (guild_id) DO UPDATE SET purge_after = excluded.purge_after",
+        )
+        .bind(guild_id as i64)
+        .bind(purge_after)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn cancel_guild_purge(&self, guild_id: u64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM pending_guild_purges WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_pending_guild_purges(&self) -> Result<Vec<(u64, i64)>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT guild_id, purge_after FROM pending_guild_purges ORDER BY purge_after",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(guild_id, purge_after)| (guild_id as u64, purge_after))
+            .collect())
+    }
+
+    async fn get_stored_guilds(&self) -> Result<Vec<(u64, u64)>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT guild_id, COUNT(*) FROM messages GROUP BY guild_id ORDER BY guild_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(guild_id, count)| (guild_id as u64, count as u64))
+            .collect())
+    }
+
+    async fn purge_guild(&self, guild_id: u64) -> Result<u64, sqlx::Error> {
+        // All at once, so a failure leaves the guild whole and still pending
+        let mut transaction = self.pool.begin().await?;
+
+        let purged = sqlx::query("DELETE FROM messages WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        sqlx::query(
+            "DELETE FROM generation_reactions WHERE message_id IN (SELECT message_id FROM generations WHERE guild_id = ?)",
+        )
+        .bind(guild_id as i64)
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query("DELETE FROM generations WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM channel_flags WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM command_rules WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM guild_settings WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM pending_guild_purges WHERE guild_id = ?")
+            .bind(guild_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(purged)
+    }
+
+    async fn log_generation(&self, generation: &Generation) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO generations (message_id, guild_id, channel_id, source_channel_id, content, seed, settings, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT (message_id) DO UPDATE SET content = excluded.content, seed = excluded.seed, settings = excluded.settings",
+        )
+        .bind(generation.message_id as i64)
+        .bind(generation.guild_id as i64)
+        .bind(generation.channel_id as i64)
+        .bind(generation.source_channel_id as i64)
+        .bind(&generation.content)
+        .bind(&generation.seed)
+        .bind(&generation.settings)
+        .bind(generation.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_generation(&self, message_id: u64) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM generation_reactions WHERE message_id = ?")
+            .bind(message_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM generations WHERE message_id = ?")
+            .bind(message_id as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await
+    }
+
+    async fn add_generation_reaction(
+        &self,
+        message_id: u64,
+        user_id: u64,
+        emoji: &str,
+        value: i64,
+    ) -> Result<Option<Generation>, sqlx::Error> {
+        let is_generation = sqlx::query("SELECT 1 FROM generations WHERE message_id = ?")
+            .bind(message_id as i64)
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+
+        if !is_generation {
+            return Ok(None);
+        }
+
+        sqlx::query(
+            "INSERT INTO generation_reactions (message_id, user_id, emoji, value) VALUES (?, ?, ?, ?) ON CONFLICT (message_id, user_id, emoji) DO NOTHING",
+        )
+        .bind(message_id as i64)
+        .bind(user_id as i64)
+        .bind(emoji)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+
+        let row = sqlx::query(
+            "SELECT generations.*, COALESCE((SELECT SUM(vote) FROM generation_votes WHERE generation_votes.message_id = generations.message_id), 0) AS score FROM generations WHERE message_id = ?",
+        )
+        .bind(message_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Some(generation(&row)))
+    }
+
+    async fn remove_generation_reactions(
+        &self,
+        message_id: u64,
+        user_id: Option<u64>,
+        emoji: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let user_id = user_id.map(|user_id| user_id as i64);
+
+        sqlx::query(
+            "DELETE FROM generation_reactions WHERE message_id = ? AND (? IS NULL OR user_id = ?) AND (? IS NULL OR emoji = ?)",
+        )
+        .bind(message_id as i64)
+        .bind(user_id)
+        .bind(user_id)
+        .bind(emoji)
+        .bind(emoji)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_generation_feedback(
+        &self,
+        guild_id: u64,
+        limit: u64,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, i64)>(
+            "SELECT generations.content, SUM(generation_votes.vote) AS score FROM generations JOIN generation_votes ON generation_votes.message_id = generations.message_id WHERE generations.guild_id = ? GROUP BY generations.message_id HAVING score != 0 ORDER BY generations.created_at DESC LIMIT ?",
+        )
+        .bind(guild_id as i64)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn get_ranked_generations(
+        &self,
+        guild_id: u64,
+        best: bool,
+        limit: u64,
+    ) -> Result<Vec<Generation>, sqlx::Error> {
+        let query = if best {
+            "SELECT generations.*, SUM(generation_votes.vote) AS score FROM generations JOIN generation_votes ON generation_votes.message_id = generations.message_id WHERE generations.guild_id = ? GROUP BY generations.message_id HAVING score > 0 ORDER BY score DESC, generations.created_at DESC LIMIT ?"
+        } else {
+            "SELECT generations.*, SUM(generation_votes.vote) AS score FROM generations JOIN generation_votes ON generation_votes.message_id = generations.message_id WHERE generations.guild_id = ? GROUP BY generations.message_id HAVING score < 0 ORDER BY score ASC, generations.created_at DESC LIMIT ?"
+        };
+
+        let rows = sqlx::query(query)
+            .bind(guild_id as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(generation).collect())
+    }
+
+    async fn feature_generation(
+        &self,
+        message_id: u64,
+        featured_at: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let featured = sqlx::query(
+            "UPDATE generations SET featured_at = ? WHERE message_id = ? AND featured_at IS NULL",
+        )
+        .bind(featured_at)
+        .bind(message_id as i64)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(featured > 0)
+    }
+
+    async fn unfeature_generation(&self, message_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE generations SET featured_at = NULL WHERE message_id = ?")
+            .bind(message_id as i64)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_featured_generations(
+        &self,
+        guild_id: u64,
+        limit: u64,
+        offset: u64,
+    ) -> Result<(u64, Vec<Generation>), sqlx::Error> {
+        let total = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM generations WHERE guild_id = ? AND featured_at IS NOT NULL",
+        )
+        .bind(guild_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let rows = sqlx::query(
+            "SELECT generations.*, COALESCE((SELECT SUM(vote) FROM generation_votes WHERE generation_votes.message_id = generations.message_id), 0) AS score FROM generations WHERE guild_id = ? AND featured_at IS NOT NULL ORDER BY featured_at DESC, message_id DESC LIMIT ? OFFSET ?",
+        )
+        .bind(guild_id as i64)
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok((total as u64, rows.iter().map(generation).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens a fresh database file, since every pooled connection to an
+    /// in-memory database would get its own.
+    async fn open(name: &str) -> SqliteDatabase {
+        let path =
+            std::env::temp_dir().join(format!("markov-bot-{}-{}.db", name, std::process::id()));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+
+        SqliteDatabase::new(&format!("sqlite:{}", path.display()))
+            .await
+            .unwrap()
+    }
+
+    fn message(message_id: u64, channel_id: u64, content: &str) -> StoredMessage {
+        StoredMessage {
+            message_id,
+            author_id: 1,
+            channel_id,
+            guild_id: 1,
+            content: content.into(),
+            is_bot: false,
+            parent_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn search_skips_channels_the_member_cant_read() {
+        let database = open("search-visibility").await;
+        database
+            .insert_messages(&[
+                message(1, 10, "the secret plan is ready"),
+                message(2, 20, "the secret plan leaked"),
+            ])
+            .await
+            .unwrap();
+
+        // Only channel 10 is readable by the member
+        let (total, found) = database
+            .search_messages(1, "secret plan", None, Some(&[10]), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert!(found.iter().all(|message| message.channel_id == 10));
+
+        // Picking the unreadable channel leaves nothing to search
+        let (total, found) = database
+            .search_messages(1, "secret plan", None, Some(&[]), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 0);
+        assert!(found.is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_skips_channels_not_generated_from() {
+        let database = open("search-flags").await;
+        database
+            .insert_messages(&[
+                message(1, 10, "the secret plan is ready"),
+                message(2, 20, "the secret plan leaked"),
+            ])
+            .await
+            .unwrap();
+        database
+            .set_channel_flags(1, 20, ChannelFlags::IGNORE)
+            .await
+            .unwrap();
+
+        let (total, found) = database
+            .search_messages(1, "secret plan", None, Some(&[10, 20]), 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(found[0].channel_id, 10);
+    }
+
+    #[tokio::test]
+    async fn search_skips_threads_of_channels_not_generated_from() {
+        let database = open("search-threads").await;
+        database
+            .insert_messages(&[
+                StoredMessage {
+                    parent_id: Some(20),
+                    ..message(1, 21, "the secret plan is in a thread")
+                },
+                StoredMessage {
+                    parent_id: Some(20),
+                    ..message(2, 22, "the secret plan is in another thread")
+                },
+            ])
+            .await
+            .unwrap();
+        database
+            .set_channel_flags(1, 20, ChannelFlags::IGNORE)
+            .await
+            .unwrap();
+
+        let (total, _) = database
+            .search_messages(1, "secret plan", None, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 0);
+
+        // A thread's own flags win over its parent's
+        let allowed = ChannelFlags {
+            generate_from: true,
+            ..ChannelFlags::IGNORE
+        };
+        database.set_channel_flags(1, 22, allowed).await.unwrap();
+        let (total, found) = database
+            .search_messages(1, "secret plan", None, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(found[0].channel_id, 22);
+    }
+
+    #[tokio::test]
+    async fn insert_skips_authors_who_opted_out_since_queueing() {
+        let database = open("insert-consent").await;
+        database
+            .insert_messages(&[message(1, 10, "said before forgetting")])
+            .await
+            .unwrap();
+
+        // The user is forgotten while their next message waits in the queue
+        assert_eq!(database.forget_user(1).await.unwrap().messages, 1);
+        database
+            .insert_messages(&[message(2, 10, "queued before forgetting")])
+            .await
+            .unwrap();
+
+        let (total, _) = database
+            .search_messages(1, "forgetting", None, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 0);
+    }
+}
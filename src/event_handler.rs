@@ -1,26 +1,143 @@
 use rand::rngs::OsRng;
-use serenity::all::CreateCommand;
-use std::sync::Arc;
-use tokio::time::Duration;
-
 use rand::Rng;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::time::Duration;
 
-use serenity::builder::GetMessages;
-use serenity::model::{application::Interaction, channel::Message, gateway::Ready};
+use serenity::model::{
+    application::Interaction,
+    channel::{Message, Reaction},
+    gateway::Ready,
+    guild::{Guild, UnavailableGuild},
+    id::{ChannelId, GuildId, MessageId},
+};
 use serenity::prelude::*;
 use serenity::{
-    all::{Command as CommandInteraction, CreateMessage},
+    all::{
+        Command as CommandInteraction, CreateInteractionResponse, CreateInteractionResponseMessage,
+        CreateMessage,
+    },
     async_trait,
 };
 
-use crate::commands::Command;
-use crate::database::Database;
-use crate::utils::helpers::{generate_markov_message, get_most_popular_channel};
+use crate::autopost::{self, ChannelActivity};
+use crate::commands;
+use crate::conversation::Conversations;
+use crate::database::{ChannelFlags, StoredMessage};
+use crate::hall_of_fame;
+use crate::maintenance::GUILD_PURGE_GRACE_PERIOD;
+use crate::message_queue::MessageQueue;
+use crate::settings::GuildSettings;
+use crate::state::AppState;
+use crate::utils::helpers::{
+    generate_markov_message, generate_markov_reply, log_generation, GeneratedMessage,
+};
+use crate::utils::{reactions, visibility};
 
 pub struct Handler {
-    pub commands: Vec<Command>,
-    pub registered: Vec<CreateCommand>,
-    pub database: Arc<Database>,
+    pub state: AppState,
+    pub message_queue: MessageQueue,
+    /// Bot and webhook authors whose stored messages were already flagged this run.
+    pub flagged_bot_authors: Mutex<HashSet<u64>>,
+    /// Whether the autopost scheduler was already spawned by an earlier ready.
+    pub autopost_started: AtomicBool,
+    /// Messages members sent in each channel since the bot last spoke there.
+    pub activity: Arc<ChannelActivity>,
+    /// Conversations members are having with the bot by replying to it.
+    pub conversations: Conversations,
+}
+
+impl Handler {
+    /// Flags the previously stored messages of a bot or webhook author, once per run.
+    async fn flag_bot_author(&self, author_id: u64) {
+        if !self.flagged_bot_authors.lock().unwrap().insert(author_id) {
+            return;
+        }
+
+        match self.state.database.flag_bot_author(author_id).await {
+            Ok(0) => {}
+            Ok(flagged) => println!("Flagged {} stored messages of bot {}", flagged, author_id),
+            Err(e) => eprintln!("Failed to flag bot messages: {}", e),
+        }
+    }
+
+    /// Answers a reply to one of the bot's messages, as its next turn in the
+    /// channel's conversation.
+    async fn converse(
+        &self,
+        ctx: &Context,
+        msg: &Message,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) {
+        let mut said = match self.conversations.take_turn(
+            msg.channel_id,
+            settings.conversation_max_turns,
+            Duration::from_secs(settings.conversation_timeout_seconds),
+        ) {
+            Some(said) => said,
+            None => return,
+        };
+
+        // Carry on from the reply, without repeating the message it replied to
+        let mut context = vec![msg.content.as_str()];
+        if let Some(referenced_message) = &msg.referenced_message {
+            context.push(referenced_message.content.as_str());
+            said.push(referenced_message.content.clone());
+        }
+
+        let reply = match generate_markov_reply(
+            guild_id,
+            msg.channel_id,
+            &context,
+            &said,
+            self.state.database.clone(),
+            settings,
+        )
+        .await
+        {
+            Some(reply) => reply,
+            None => return,
+        };
+
+        self.conversations
+            .said(msg.channel_id, reply.content.clone());
+        self.post_generated(ctx, guild_id, msg.channel_id, &reply, Some(msg), settings)
+            .await;
+    }
+
+    /// Posts a generated message, as a reply to `reply_to` if given, and logs
+    /// it so reactions to it are recorded.
+    async fn post_generated(
+        &self,
+        ctx: &Context,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        generated: &GeneratedMessage,
+        reply_to: Option<&Message>,
+        settings: &GuildSettings,
+    ) {
+        let mut builder = CreateMessage::new().content(&generated.content);
+        if let Some(reply_to) = reply_to {
+            builder = builder.reference_message(reply_to);
+        }
+
+        match channel_id.send_message(&ctx.http, builder).await {
+            Ok(posted) => {
+                log_generation(
+                    self.state.database.as_ref(),
+                    guild_id,
+                    &posted,
+                    channel_id,
+                    generated,
+                    settings,
+                )
+                .await
+            }
+            Err(e) => eprintln!("Failed to post a generated message: {}", e),
+        }
+    }
 }
 
 #[async_trait]
@@ -28,81 +145,68 @@ impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, bot: Ready) {
         println!("Bot has started as {}", bot.user.name);
 
-        match CommandInteraction::set_global_commands(&ctx.http, self.registered.clone()).await {
-            Err(e) => {
-                eprintln!("There was an error while registering commands: {}", e);
-            }
-            Ok(_) => {}
-        }
-
-        // Random message generator on loop
-        let mut rng = OsRng;
-        let database_clone = self.database.clone();
-        tokio::spawn(async move {
-            loop {
-                // Fetch vector of guilds the bot is in.
-                let guild_ids = ctx.cache.guilds();
-
-                // Loop over the guild ids
-                for guild_id in guild_ids {
-                    // Get the channel id of the most popular channel
-                    let popular_channel_id =
-                        get_most_popular_channel(guild_id, database_clone.clone()).await;
-                    let all_channels = ctx.http.get_channels(guild_id).await.unwrap();
-
-                    if let Some(channel_id) = all_channels
-                        .iter()
-                        .find(|channel| channel.id.get() == popular_channel_id)
-                        .map(|channel| channel.id)
-                    {
-                        // Fetch the channel
-                        let channel = ctx.http.get_channel(channel_id).await.unwrap();
-
-                        match channel.guild() {
-                            Some(channel) => {
-                                let messages = channel
-                                    .messages(&ctx.http, GetMessages::new().limit(100))
-                                    .await
-                                    .unwrap();
-
-                                let mut messages_have_bot = false;
-                                for message in messages {
-                                    if message.author.id.get() == ctx.cache.current_user().id.get()
-                                    {
-                                        messages_have_bot = true;
-                                    }
-                                }
-
-                                // Only send a message if builder is not None
-                                if let Some(markov_message) = generate_markov_message(
-                                    guild_id,
-                                    channel.id,
-                                    None,
-                                    database_clone.clone(),
-                                )
-                                .await
-                                {
-                                    if !messages_have_bot {
-                                        channel
-                                            .send_message(
-                                                &ctx.http,
-                                                CreateMessage::new().content(markov_message),
-                                            )
-                                            .await
-                                            .unwrap();
-                                    }
-                                }
-                            }
-                            None => {}
-                        }
-                    }
+        if self.state.command_guilds.is_empty() {
+            match CommandInteraction::set_global_commands(&ctx.http, commands::register_vecs())
+                .await
+            {
+                Err(e) => {
+                    eprintln!("There was an error while registering commands: {}", e);
                 }
+                Ok(_) => {}
+            }
+        } else {
+            // Global commands from earlier runs would show up twice
+            if let Err(e) = CommandInteraction::set_global_commands(&ctx.http, Vec::new()).await {
+                eprintln!("There was an error while clearing global commands: {}", e);
+            }
 
-                // Wait a random second from 300 to 900
-                let range = rng.gen_range(300..900);
-                tokio::time::sleep(Duration::from_secs(range)).await;
+            for &guild_id in &self.state.command_guilds {
+                commands::register_guild_commands(&ctx.http, &self.state, guild_id).await;
             }
-        });
+        }
+
+        // Our own messages were stored before bots were told apart, keep them out of training
+        self.flag_bot_author(bot.user.id.get()).await;
+
+        // Ready fires again after reconnects, only ever run one scheduler
+        if !self.autopost_started.swap(true, Ordering::SeqCst) {
+            autopost::spawn(ctx, self.state.database.clone(), self.activity.clone());
+        }
+    }
+
+    async fn guild_create(&self, _ctx: Context, guild: Guild, _is_new: Option<bool>) {
+        // The bot is (back) in this guild, so keep its data
+        match self.state.database.cancel_guild_purge(guild.id.get()).await {
+            Ok(true) => println!("Cancelled the scheduled purge of guild {}", guild.id),
+            Ok(false) => {}
+            Err(e) => eprintln!("Failed to cancel guild purge: {}", e),
+        }
+    }
+
+    async fn guild_delete(
+        &self,
+        _ctx: Context,
+        incomplete: UnavailableGuild,
+        _full: Option<Guild>,
+    ) {
+        // Outages also send this event, only purge when the bot was actually removed
+        if incomplete.unavailable {
+            return;
+        }
+
+        let purge_after = chrono::Utc::now() + GUILD_PURGE_GRACE_PERIOD;
+        match self
+            .state
+            .database
+            .schedule_guild_purge(incomplete.id.get(), purge_after.timestamp())
+            .await
+        {
+            Ok(_) => println!(
+                "Removed from guild {}, its data will be purged after {}",
+                incomplete.id, purge_after
+            ),
+            Err(e) => eprintln!("Failed to schedule guild purge: {}", e),
+        }
     }
 
     async fn message(&self, ctx: Context, msg: Message) {
@@ -111,21 +215,75 @@ impl EventHandler for Handler {
             _ => return,
         };
 
-        if let Err(e) = self
-            .database
-            .insert_message(
-                msg.id.get(),
-                msg.author.id.get(),
-                msg.channel_id.get(),
+        // Never learn from our own output
+        if msg.author.id == ctx.cache.current_user().id {
+            self.activity.reset(msg.channel_id);
+            return;
+        }
+
+        // Treat the channel as ignored if its flags can't be read
+        let parent_id = visibility::thread_parent(&ctx.cache, guild_id, msg.channel_id);
+        let flags = match self
+            .state
+            .lookups
+            .channel_flags(
+                self.state.database.as_ref(),
                 guild_id.get(),
-                &msg.content,
+                msg.channel_id.get(),
+                parent_id,
             )
             .await
         {
-            eprintln!("Failed to insert message into database: {}", e);
+            Ok(flags) => flags,
+            Err(e) => {
+                eprintln!("Failed to get channel flags: {}", e);
+                ChannelFlags::IGNORE
+            }
+        };
+
+        let is_bot = msg.author.bot || msg.webhook_id.is_some();
+
+        // Bot and webhook messages are only stored if the guild opted in
+        let collect = if !flags.collect {
+            false
+        } else if is_bot {
+            self.flag_bot_author(msg.author.id.get()).await;
+            self.state
+                .lookups
+                .settings(self.state.database.as_ref(), guild_id.get())
+                .await
+                .store_bot_messages
+        } else {
+            // Don't collect messages from users who opted out
+            match self
+                .state
+                .lookups
+                .is_opted_out(self.state.database.as_ref(), msg.author.id.get())
+                .await
+            {
+                Ok(opted_out) => !opted_out,
+                Err(e) => {
+                    eprintln!("Failed to check user consent: {}", e);
+                    false
+                }
+            }
+        };
+
+        if collect {
+            self.message_queue
+                .push(StoredMessage {
+                    message_id: msg.id.get(),
+                    author_id: msg.author.id.get(),
+                    channel_id: msg.channel_id.get(),
+                    guild_id: guild_id.get(),
+                    content: msg.content.clone(),
+                    is_bot,
+                    parent_id,
+                })
+                .await;
         }
 
-        if msg.author.bot {
+        if is_bot {
             return;
         }
 
@@ -137,43 +295,268 @@ impl EventHandler for Handler {
             }
         }
 
+        // Don't answer from channels whose content must not be generated from
+        if !flags.generate_from {
+            return;
+        }
+
+        let settings = self
+            .state
+            .lookups
+            .settings(self.state.database.as_ref(), guild_id.get())
+            .await;
+
+        // Replies to the bot carry on a conversation, if the guild turned them on.
+        // Such replies usually mention the bot too, so the turn limit wins over that
+        let replies_to_bot = msg
+            .referenced_message
+            .as_ref()
+            .is_some_and(|referenced_message| {
+                referenced_message.author.id == ctx.cache.current_user().id
+            });
+        if replies_to_bot && settings.conversation_max_turns > 0 {
+            self.converse(&ctx, &msg, guild_id, &settings).await;
+            return;
+        }
+
         if msg.mentions_me(&ctx.http).await.unwrap_or(false) {
-            let builder = match generate_markov_message(
+            // Answer what was said, and what it replied to
+            let mut context = vec![msg.content.as_str()];
+            if let Some(referenced_message) = &msg.referenced_message {
+                context.push(referenced_message.content.as_str());
+            }
+
+            match generate_markov_reply(
                 guild_id,
                 msg.channel_id,
-                None,
-                self.database.clone(),
+                &context,
+                &[],
+                self.state.database.clone(),
+                &settings,
             )
             .await
             {
-                Some(markov_message) => CreateMessage::new().content(markov_message),
-                None => CreateMessage::new()
-                    .content("Please wait until this channel has over 500 messages."),
-            };
+                Some(reply) => {
+                    self.post_generated(
+                        &ctx,
+                        guild_id,
+                        msg.channel_id,
+                        &reply,
+                        Some(&msg),
+                        &settings,
+                    )
+                    .await
+                }
+                None => {
+                    let builder = CreateMessage::new()
+                        .content(format!(
+                            "Please wait until this channel has over {} messages.",
+                            settings.min_messages
+                        ))
+                        .reference_message(&msg);
+                    if let Err(e) = msg.channel_id.send_message(&ctx.http, builder).await {
+                        eprintln!("Failed to reply to a mention: {}", e);
+                    }
+                }
+            }
+            return;
+        }
 
-            msg.channel_id
-                .send_message(&ctx.http, builder)
-                .await
-                .unwrap();
+        // Count towards the next activity-triggered message, and maybe chime in anyway
+        let activity_due = self
+            .activity
+            .record(msg.channel_id, settings.autopost_after_messages);
+        let chime_in = settings.reply_chance_percent > 0
+            && OsRng.gen_range(0..100) < settings.reply_chance_percent;
+
+        if !(activity_due || chime_in)
+            || !autopost::may_post_unprompted(&settings, &flags, msg.channel_id)
+        {
+            return;
+        }
+
+        if let Some(generated) = generate_markov_message(
+            guild_id,
+            msg.channel_id,
+            None,
+            self.state.database.clone(),
+            &settings,
+        )
+        .await
+        {
+            self.post_generated(&ctx, guild_id, msg.channel_id, &generated, None, &settings)
+                .await;
+        }
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let value = reactions::value(&reaction.emoji);
+        let user_id = match reaction.user_id {
+            Some(user_id) => user_id,
+            None => return,
+        };
+
+        if value == 0 || reaction.guild_id.is_none() || user_id == ctx.cache.current_user().id {
+            return;
+        }
+
+        // Reactions are data about the user too, respect their opt-out
+        match self
+            .state
+            .lookups
+            .is_opted_out(self.state.database.as_ref(), user_id.get())
+            .await
+        {
+            Ok(false) => {}
+            Ok(true) => return,
+            Err(e) => {
+                eprintln!("Failed to check user consent: {}", e);
+                return;
+            }
+        }
+
+        match self
+            .state
+            .database
+            .add_generation_reaction(
+                reaction.message_id.get(),
+                user_id.get(),
+                &reaction.emoji.to_string(),
+                value,
+            )
+            .await
+        {
+            Ok(Some(generation)) => {
+                hall_of_fame::consider(&ctx, self.state.database.as_ref(), &generation).await
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to record reaction: {}", e),
+        }
+    }
+
+    async fn reaction_remove(&self, _ctx: Context, reaction: Reaction) {
+        if reactions::value(&reaction.emoji) == 0 {
             return;
         }
+
+        let user_id = match reaction.user_id {
+            Some(user_id) => user_id.get(),
+            None => return,
+        };
+
+        if let Err(e) = self
+            .state
+            .database
+            .remove_generation_reactions(
+                reaction.message_id.get(),
+                Some(user_id),
+                Some(&reaction.emoji.to_string()),
+            )
+            .await
+        {
+            eprintln!("Failed to remove reaction: {}", e);
+        }
+    }
+
+    async fn reaction_remove_all(
+        &self,
+        _ctx: Context,
+        _channel_id: ChannelId,
+        removed_from_message_id: MessageId,
+    ) {
+        if let Err(e) = self
+            .state
+            .database
+            .remove_generation_reactions(removed_from_message_id.get(), None, None)
+            .await
+        {
+            eprintln!("Failed to remove reactions: {}", e);
+        }
+    }
+
+    async fn reaction_remove_emoji(&self, _ctx: Context, removed_reactions: Reaction) {
+        if let Err(e) = self
+            .state
+            .database
+            .remove_generation_reactions(
+                removed_reactions.message_id.get(),
+                None,
+                Some(&removed_reactions.emoji.to_string()),
+            )
+            .await
+        {
+            eprintln!("Failed to remove reactions: {}", e);
+        }
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Command(interaction) = interaction {
-            for command in &self.commands {
-                if interaction.data.name.as_str() == command.name {
-                    // Execute command
-                    if let Err(reason) =
-                        (command.exec)(&ctx, &interaction, self.database.clone()).await
-                    {
-                        println!(
-                            "There was an error while handling command {}: {:#?}",
-                            command.name, reason
-                        )
+        match interaction {
+            Interaction::Command(interaction) => {
+                let command = match commands::find(&interaction.data.name, interaction.data.kind) {
+                    Some(command) => command,
+                    None => return,
+                };
+
+                if let Err(reason) = commands::authorize(
+                    command,
+                    interaction.guild_id,
+                    interaction.member.as_deref(),
+                    interaction.channel_id,
+                    &self.state,
+                )
+                .await
+                {
+                    let response = CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(reason)
+                            .ephemeral(true),
+                    );
+                    if let Err(e) = interaction.create_response(&ctx.http, response).await {
+                        eprintln!("Failed to respond to a held back command: {}", e);
                     }
+                    return;
+                }
+
+                // Execute command
+                if let Err(reason) = command.execute(&ctx, &interaction, &self.state).await {
+                    println!(
+                        "There was an error while handling command {}: {:#?}",
+                        command.name(),
+                        reason
+                    )
+                }
+            }
+            Interaction::Autocomplete(interaction) => {
+                let command = match commands::find(&interaction.data.name, interaction.data.kind) {
+                    Some(command) => command,
+                    None => return,
+                };
+
+                if let Err(reason) = command.autocomplete(&ctx, &interaction, &self.state).await {
+                    println!(
+                        "There was an error while suggesting options for {}: {:#?}",
+                        command.name(),
+                        reason
+                    )
+                }
+            }
+            Interaction::Component(interaction) => {
+                let command = match commands::find_component(&interaction.data.custom_id) {
+                    Some(command) => command,
+                    None => return,
+                };
+
+                if let Err(reason) = command
+                    .handle_component(&ctx, &interaction, &self.state)
+                    .await
+                {
+                    println!(
+                        "There was an error while handling component {}: {:#?}",
+                        interaction.data.custom_id, reason
+                    )
                 }
             }
+            _ => {}
         }
     }
 }
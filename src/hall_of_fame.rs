@@ -0,0 +1,76 @@
+use serenity::all::{ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId};
+use serenity::prelude::*;
+
+use crate::database::{Database, Generation};
+use crate::settings::GuildSettings;
+use crate::utils::visibility;
+
+/// Reposts a generated message to the guild's hall of fame, once its score
+/// reaches the threshold. Each message is only ever reposted once.
+pub async fn consider(ctx: &Context, database: &Database, generation: &Generation) {
+    if generation.featured_at.is_some() {
+        return;
+    }
+
+    let settings = GuildSettings::load(database, generation.guild_id).await;
+
+    let channel_id = match settings.hall_of_fame_channel {
+        Some(channel_id) if generation.score >= settings.hall_of_fame_threshold => {
+            ChannelId::new(channel_id)
+        }
+        _ => return,
+    };
+
+    // Don't carry words from a private channel into the hall of fame
+    let readable = visibility::readable_by_everyone(&ctx.cache, GuildId::new(generation.guild_id));
+    if !readable.contains(&generation.source_channel_id) {
+        return;
+    }
+
+    // Claim it first, so reactions arriving at the same time don't repost it twice
+    match database
+        .feature_generation(generation.message_id, chrono::Utc::now().timestamp())
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            eprintln!("Failed to feature generation: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed(generation)))
+        .await
+    {
+        eprintln!("Failed to post to the hall of fame: {}", e);
+
+        // Let the next reaction try again
+        if let Err(e) = database.unfeature_generation(generation.message_id).await {
+            eprintln!("Failed to unfeature generation: {}", e);
+        }
+    }
+}
+
+/// Shows a generated message as it is reposted to the hall of fame.
+pub fn embed(generation: &Generation) -> CreateEmbed {
+    CreateEmbed::new()
+        .description(format!(
+            "{}\n\n[Jump to message]({})",
+            generation.content,
+            jump_link(generation)
+        ))
+        .field("Score", format!("{:+}", generation.score), true)
+        .field("Channel", format!("<#{}>", generation.channel_id), true)
+        .footer(CreateEmbedFooter::new("Hall of Fame"))
+        .color(0xFEE75C)
+}
+
+/// Links to the posted message of a generation.
+pub fn jump_link(generation: &Generation) -> String {
+    format!(
+        "https://discord.com/channels/{}/{}/{}",
+        generation.guild_id, generation.channel_id, generation.message_id
+    )
+}
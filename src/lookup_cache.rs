@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use tokio::time::{Duration, Instant};
+
+use crate::database::{ChannelFlags, Database};
+use crate::settings::GuildSettings;
+
+/// How long a looked up value is trusted. Commands that change one forget it
+/// right away, this only bounds how long changes made elsewhere (such as from
+/// the command line) take to be picked up.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Values fetched for a while, keyed by what they were fetched for.
+struct Entries<K, V> {
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K, V> Default for Entries<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> Entries<K, V> {
+    fn get(&self, key: &K) -> Option<V> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < CACHE_TTL)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Caches a freshly fetched value, forgetting stale ones.
+    fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (fetched_at, _)| fetched_at.elapsed() < CACHE_TTL);
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    fn remove_where(&self, forget: impl Fn(&K) -> bool) {
+        self.entries.lock().unwrap().retain(|key, _| !forget(key));
+    }
+}
+
+/// Guild settings, channel flags and opt-outs, which every incoming message
+/// needs but rarely change.
+#[derive(Default)]
+pub struct LookupCache {
+    settings: Entries<u64, GuildSettings>,
+    channel_flags: Entries<(u64, u64), ChannelFlags>,
+    opted_out: Entries<u64, bool>,
+}
+
+impl LookupCache {
+    /// Loads a guild's settings, see `GuildSettings::load`.
+    pub async fn settings(&self, database: &Database, guild_id: u64) -> GuildSettings {
+        if let Some(settings) = self.settings.get(&guild_id) {
+            return settings;
+        }
+
+        let settings = GuildSettings::load(database, guild_id).await;
+        self.settings.insert(guild_id, settings.clone());
+        settings
+    }
+
+    /// Returns the flags of a channel, see `Storage::get_channel_flags`.
+    /// Failed lookups aren't cached.
+    pub async fn channel_flags(
+        &self,
+        database: &Database,
+        guild_id: u64,
+        channel_id: u64,
+        parent_id: Option<u64>,
+    ) -> Result<ChannelFlags, sqlx::Error> {
+        if let Some(flags) = self.channel_flags.get(&(guild_id, channel_id)) {
+            return Ok(flags);
+        }
+
+        let flags = database
+            .get_channel_flags(guild_id, channel_id, parent_id)
+            .await?;
+        self.channel_flags.insert((guild_id, channel_id), flags);
+        Ok(flags)
+    }
+
+    /// Returns whether a user opted out. Failed lookups aren't cached.
+    pub async fn is_opted_out(
+        &self,
+        database: &Database,
+        user_id: u64,
+    ) -> Result<bool, sqlx::Error> {
+        if let Some(opted_out) = self.opted_out.get(&user_id) {
+            return Ok(opted_out);
+        }
+
+        let opted_out = database.is_opted_out(user_id).await?;
+        self.opted_out.insert(user_id, opted_out);
+        Ok(opted_out)
+    }
+
+    /// Forgets a guild's settings, after they were changed.
+    pub fn forget_settings(&self, guild_id: u64) {
+        self.settings.remove_where(|cached| *cached == guild_id);
+    }
+
+    /// Forgets the flags of every channel of a guild after one was changed,
+    /// since its threads may have taken them too.
+    pub fn forget_channel_flags(&self, guild_id: u64) {
+        self.channel_flags
+            .remove_where(|(cached, _)| *cached == guild_id);
+    }
+
+    /// Forgets whether a user opted out, after they opted in or out.
+    pub fn forget_user(&self, user_id: u64) {
+        self.opted_out.remove_where(|cached| *cached == user_id);
+    }
+}
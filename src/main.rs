@@ -1,11 +1,21 @@
 use dotenv::dotenv;
 use serenity::prelude::*;
 use std::env;
-use std::sync::Arc;
 
+mod autopost;
+mod backup;
+mod cli;
 mod commands;
+mod conversation;
+mod cooldowns;
 mod database;
 mod event_handler;
+mod hall_of_fame;
+mod lookup_cache;
+mod maintenance;
+mod message_queue;
+mod settings;
+mod state;
 mod utils;
 
 #[tokio::main]
@@ -13,32 +23,109 @@ async fn main() {
     // Load the environment variables
     dotenv().ok();
 
+    // SQLite unless a PostgreSQL URL is configured
+    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:data.db".into());
+
+    // Run a CLI subcommand instead of the bot if one was given
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(reason) = cli::run(&args, &database_url).await {
+            eprintln!("{}", reason);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Swap in a snapshot scheduled with `/backup restore` before the database is opened.
+    // Stop when that fails, rather than quietly running on the wrong data
+    match backup::restore_scheduled(&database_url) {
+        Ok(Some(snapshot)) => println!("Restored the database from {}", snapshot),
+        Ok(None) => {}
+        Err(reason) => {
+            eprintln!("{}", reason);
+            std::process::exit(1);
+        }
+    }
+
     // Initialize database
-    let database = Arc::new(
-        database::Database::new("sqlite:data.db")
-            .await
-            .expect("Failed to initialize database"),
-    );
+    let database = database::connect(&database_url)
+        .await
+        .expect("Failed to initialize database");
 
     let discord_token =
         env::var("DISCORD_TOKEN").expect("Expected DISCORD_TOKEN to be defined in environment.");
 
-    let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
-    let commands = commands::commands_vecs();
-    let registered = commands::register_vecs();
+    // Enforce retention policies and keep the database tidy in the background
+    maintenance::spawn(database.clone());
+
+    // Messages are written in batches by a background task
+    let (message_queue, message_queue_handle) =
+        message_queue::MessageQueue::spawn(database.clone());
+
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::MESSAGE_CONTENT;
+    let state = state::AppState {
+        database: database.clone(),
+        command_guilds: commands::command_guilds(),
+        vocabularies: Default::default(),
+        cooldowns: Default::default(),
+        lookups: Default::default(),
+    };
 
     // Build the Discord client, and pass in our event handler
     let mut client = Client::builder(discord_token, intents)
         .event_handler(event_handler::Handler {
-            commands,
-            registered,
-            database: database.clone(),
+            state,
+            message_queue,
+            flagged_bot_authors: Default::default(),
+            autopost_started: Default::default(),
+            activity: Default::default(),
+            conversations: Default::default(),
         })
         .await
         .expect("Error creating client.");
 
+    // Disconnect cleanly on Ctrl+C or SIGTERM, so queued messages can be flushed
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        println!("Shutting down...");
+        shard_manager.shutdown_all().await;
+    });
+
     // Run the Discord client (runs the ready function)
     if let Err(reason) = client.start().await {
         println!("Error starting client: {:?}", reason);
     }
+
+    // Drop the client so the event handler stops producing, then flush the queue
+    drop(client);
+    message_queue_handle.shutdown().await;
+}
+
+/// Waits for Ctrl+C, or for SIGTERM from service managers and `docker stop`.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(reason) => eprintln!("Failed to listen for SIGTERM: {}", reason),
+        }
+    }
+
+    if let Err(reason) = tokio::signal::ctrl_c().await {
+        eprintln!("Failed to listen for Ctrl+C: {}", reason);
+        // Never resolve, rather than shutting down right away
+        std::future::pending::<()>().await;
+    }
 }
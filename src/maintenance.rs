@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use chrono::{Duration as ChronoDuration, Utc};
+use tokio::time::{Duration, Instant};
+
+use crate::backup;
+use crate::database::Database;
+use crate::settings::GuildSettings;
+use crate::utils::snowflake;
+
+/// How often retention policies are enforced.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often the query planner statistics are refreshed.
+const ANALYZE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often the database file is compacted.
+const VACUUM_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How often a snapshot of the database is taken.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a guild's data is kept after the bot is removed from it, in
+/// case it was removed by mistake and gets added back.
+pub const GUILD_PURGE_GRACE_PERIOD: ChronoDuration = ChronoDuration::days(7);
+
+/// Spawns the background task that prunes messages according to each guild's
+/// retention settings, purges guilds the bot has left, keeps the database tidy,
+/// and takes rotating backups.
+pub fn spawn(database: Arc<Database>) {
+    tokio::spawn(async move {
+        let mut last_analyze = Instant::now();
+        let mut last_vacuum = Instant::now();
+        let mut last_backup = Instant::now();
+
+        loop {
+            tokio::time::sleep(PRUNE_INTERVAL).await;
+
+            prune(database.as_ref()).await;
+            purge_departed_guilds(database.as_ref()).await;
+
+            let vacuum = last_vacuum.elapsed() >= VACUUM_INTERVAL;
+            if vacuum || last_analyze.elapsed() >= ANALYZE_INTERVAL {
+                match database.optimize(vacuum).await {
+                    Ok(_) if vacuum => println!("Maintenance: vacuumed and analyzed the database"),
+                    Ok(_) => println!("Maintenance: analyzed the database"),
+                    Err(e) => eprintln!("Failed to optimize database: {}", e),
+                }
+
+                last_analyze = Instant::now();
+                if vacuum {
+                    last_vacuum = Instant::now();
+                }
+            }
+
+            if last_backup.elapsed() >= BACKUP_INTERVAL {
+                back_up(database.as_ref()).await;
+                last_backup = Instant::now();
+            }
+        }
+    });
+}
+
+/// Enforces the retention settings of every guild that has configured them.
+async fn prune(database: &Database) {
+    let guild_ids = match database.get_configured_guilds().await {
+        Ok(guild_ids) => guild_ids,
+        Err(e) => {
+            eprintln!("Failed to fetch configured guilds: {}", e);
+            return;
+        }
+    };
+
+    for guild_id in guild_ids {
+        let settings = GuildSettings::load(database, guild_id).await;
+
+        if settings.retention_max_age_days > 0 {
+            let cutoff = Utc::now() - ChronoDuration::days(settings.retention_max_age_days as i64);
+
+            match database
+                .prune_messages_before(guild_id, snowflake::from_datetime(cutoff))
+                .await
+            {
+                Ok(0) => {}
+                Ok(pruned) => println!(
+                    "Maintenance: pruned {} messages older than {} days from guild {}",
+                    pruned, settings.retention_max_age_days, guild_id
+                ),
+                Err(e) => eprintln!("Failed to prune old messages of guild {}: {}", guild_id, e),
+            }
+        }
+
+        if settings.retention_max_rows_per_channel > 0 {
+            match database
+                .prune_channel_overflow(guild_id, settings.retention_max_rows_per_channel)
+                .await
+            {
+                Ok(0) => {}
+                Ok(pruned) => println!(
+                    "Maintenance: pruned {} messages over the {} per channel limit from guild {}",
+                    pruned, settings.retention_max_rows_per_channel, guild_id
+                ),
+                Err(e) => eprintln!(
+                    "Failed to prune channel overflow of guild {}: {}",
+                    guild_id, e
+                ),
+            }
+        }
+    }
+}
+
+/// Purges the data of guilds whose grace period after removing the bot has passed.
+async fn purge_departed_guilds(database: &Database) {
+    let pending = match database.get_pending_guild_purges().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            eprintln!("Failed to fetch pending guild purges: {}", e);
+            return;
+        }
+    };
+
+    let now = Utc::now().timestamp();
+
+    for (guild_id, purge_after) in pending {
+        if purge_after > now {
+            continue;
+        }
+
+        match database.purge_guild(guild_id).await {
+            Ok(purged) => println!(
+                "Maintenance: purged {} messages from guild {} after the bot was removed",
+                purged, guild_id
+            ),
+            Err(e) => eprintln!("Failed to purge guild {}: {}", guild_id, e),
+        }
+    }
+}
+
+/// Takes a snapshot of the database and deletes the oldest ones over the limit.
+async fn back_up(database: &Database) {
+    let keep = backup::keep();
+    if keep == 0 {
+        return;
+    }
+
+    match backup::create(database).await {
+        Ok(path) => println!("Maintenance: backed up the database to {}", path.display()),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    }
+
+    match backup::rotate(keep) {
+        Ok(0) => {}
+        Ok(deleted) => println!("Maintenance: deleted {} old backups", deleted),
+        Err(e) => eprintln!("Failed to rotate backups: {}", e),
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::database::{Database, StoredMessage};
+
+/// How many messages can wait in the queue before `push` starts waiting.
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Maximum amount of messages written in one transaction.
+const MAX_BATCH_SIZE: usize = 500;
+
+/// How long a message may wait in the queue before its batch is written.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to wait before retrying a batch that failed to be written, doubled
+/// after every failure up to `MAX_RETRY_DELAY`.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How many times a batch is written before it's split up to find the
+/// messages that can't be, about half a minute of retrying.
+const MAX_ATTEMPTS: u32 = 6;
+
+/// How long shutdown waits for the queued messages to be written.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Collects incoming messages and writes them to the database in batches, so
+/// the gateway event tasks never wait on a write.
+#[derive(Clone)]
+pub struct MessageQueue {
+    sender: mpsc::Sender<StoredMessage>,
+}
+
+/// Owns the writer task, used to flush the remaining messages at shutdown.
+pub struct MessageQueueHandle {
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl MessageQueue {
+    /// Spawns the writer task.
+    pub fn spawn(database: Arc<Database>) -> (Self, MessageQueueHandle) {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+
+        let task = tokio::spawn(run_writer(database, receiver, shutdown_receiver));
+
+        (Self { sender }, MessageQueueHandle { shutdown, task })
+    }
+
+    /// Queues a message to be written. Waits when the queue is full, so a
+    /// struggling database slows down intake instead of growing memory.
+    pub async fn push(&self, message: StoredMessage) {
+        if self.sender.send(message).await.is_err() {
+            eprintln!("Message queue is closed, dropping message");
+        }
+    }
+}
+
+impl MessageQueueHandle {
+    /// Stops accepting messages and waits until everything queued is written.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        if let Err(e) = self.task.await {
+            eprintln!("Message queue writer stopped unexpectedly: {}", e);
+        }
+    }
+}
+
+async fn run_writer(
+    database: Arc<Database>,
+    mut receiver: mpsc::Receiver<StoredMessage>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let mut batch = Vec::with_capacity(MAX_BATCH_SIZE);
+
+    loop {
+        // Wait for the first message of the next batch
+        let first = tokio::select! {
+            message = receiver.recv() => message,
+            _ = &mut shutdown => break,
+        };
+
+        match first {
+            Some(message) => batch.push(message),
+            None => break,
+        }
+
+        // Then keep collecting until the batch is full or old enough
+        let deadline = Instant::now() + FLUSH_INTERVAL;
+        while batch.len() < MAX_BATCH_SIZE {
+            tokio::select! {
+                message = receiver.recv() => match message {
+                    Some(message) => batch.push(message),
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+        }
+
+        flush(database.as_ref(), &mut batch, MAX_ATTEMPTS).await;
+    }
+
+    // Write out whatever is still queued, without waiting between attempts
+    receiver.close();
+    let drain = async {
+        while let Some(message) = receiver.recv().await {
+            batch.push(message);
+            if batch.len() >= MAX_BATCH_SIZE {
+                flush(database.as_ref(), &mut batch, 1).await;
+            }
+        }
+        flush(database.as_ref(), &mut batch, 1).await;
+    };
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, drain).await.is_err() {
+        eprintln!(
+            "Gave up writing the queued messages after {}s, dropping the rest",
+            SHUTDOWN_TIMEOUT.as_secs()
+        );
+    }
+}
+
+/// Writes the batch, retrying with a growing delay since a failed write is
+/// usually the database being busy, such as during a vacuum. A batch the
+/// database keeps rejecting is split up, so only the messages that can't be
+/// written are dropped, and one it can't be reached for is dropped whole.
+async fn flush(database: &Database, batch: &mut Vec<StoredMessage>, attempts: u32) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let mut delay = INITIAL_RETRY_DELAY;
+    for attempt in 1..=attempts {
+        let e = match database.insert_messages(batch).await {
+            Ok(()) => break,
+            Err(e) => e,
+        };
+
+        if attempt < attempts {
+            eprintln!(
+                "Failed to insert {} messages into database, retrying in {}s: {}",
+                batch.len(),
+                delay.as_secs(),
+                e
+            );
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        } else if matches!(e, sqlx::Error::Database(_)) && batch.len() > 1 {
+            eprintln!(
+                "Failed to insert {} messages into database, splitting the batch: {}",
+                batch.len(),
+                e
+            );
+            split(database, batch).await;
+        } else {
+            eprintln!(
+                "Failed to insert {} messages into database, dropping them: {}",
+                batch.len(),
+                e
+            );
+        }
+    }
+
+    batch.clear();
+}
+
+/// Writes the halves of a rejected batch, and the halves of those that are
+/// rejected too, until only the messages the database won't take are left.
+async fn split(database: &Database, batch: &[StoredMessage]) {
+    let (first, second) = batch.split_at(batch.len() / 2);
+    let mut pending = vec![second, first];
+
+    while let Some(chunk) = pending.pop() {
+        match database.insert_messages(chunk).await {
+            Ok(()) => {}
+            Err(sqlx::Error::Database(_)) if chunk.len() > 1 => {
+                let (first, second) = chunk.split_at(chunk.len() / 2);
+                pending.push(second);
+                pending.push(first);
+            }
+            Err(e) => eprintln!(
+                "Failed to insert {} messages into database, dropping them: {}",
+                chunk.len(),
+                e
+            ),
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+
+use crate::database::Database;
+
+/// The kind of value a setting holds, used to validate `/config set`.
+pub enum SettingKind {
+    Integer {
+        min: i64,
+        max: i64,
+    },
+    Boolean,
+    /// Whitespace separated words, since commas are valid prefixes themselves.
+    List {
+        max_items: usize,
+    },
+    /// Whitespace separated channel mentions or ids, stored as ids.
+    Channels {
+        max_items: usize,
+    },
+    /// An IANA timezone name, such as `Europe/Berlin`.
+    Timezone,
+    /// Comma separated command names, since context menu names contain spaces.
+    Commands,
+}
+
+/// A per-guild setting that can be changed with `/config`.
+pub struct Setting {
+    pub key: &'static str,
+    pub description: &'static str,
+    pub kind: SettingKind,
+    pub default: &'static str,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "retention_max_age_days",
+        description: "Delete stored messages older than this many days, 0 keeps them forever",
+        kind: SettingKind::Integer { min: 0, max: 36500 },
+        default: "0",
+    },
+    Setting {
+        key: "retention_max_rows_per_channel",
+        description: "Keep at most this many stored messages per channel, 0 is unlimited",
+        kind: SettingKind::Integer {
+            min: 0,
+            max: 100_000_000,
+        },
+        default: "0",
+    },
+    Setting {
+        key: "min_messages",
+        description: "How many usable messages a channel needs before messages are generated from it",
+        kind: SettingKind::Integer {
+            min: 10,
+            max: 50_000,
+        },
+        default: "500",
+    },
+    Setting {
+        key: "fetch_limit",
+        description: "How many messages are sampled to build each generated message",
+        kind: SettingKind::Integer {
+            min: 100,
+            max: 50_000,
+        },
+        default: "5000",
+    },
+    Setting {
+        key: "blacklist_prefixes",
+        description: "Messages and words starting with any of these, separated by spaces, are ignored by generation, /guess and /leaderboard",
+        kind: SettingKind::List { max_items: 50 },
+        default: "$ & ! . m. > < [ ] @ # % ^ * , https http",
+    },
+    Setting {
+        key: "game_timeout_seconds",
+        description: "How long a /guess round waits for a guess before the game ends",
+        kind: SettingKind::Integer { min: 30, max: 3600 },
+        default: "180",
+    },
+    Setting {
+        key: "autopost_enabled",
+        description: "Post generated messages on a schedule, also changed by /autopost pause and resume",
+        kind: SettingKind::Boolean,
+        default: "true",
+    },
+    Setting {
+        key: "autopost_interval_seconds",
+        description: "Average wait between automatic messages",
+        kind: SettingKind::Integer {
+            min: 60,
+            max: 604_800,
+        },
+        default: "600",
+    },
+    Setting {
+        key: "autopost_jitter_seconds",
+        description: "How much each wait between automatic messages may randomly differ from the interval",
+        kind: SettingKind::Integer {
+            min: 0,
+            max: 604_800,
+        },
+        default: "300",
+    },
+    Setting {
+        key: "autopost_after_messages",
+        description: "Post after this many messages from members since the bot last spoke in a channel, instead of on a timer, 0 uses the timer",
+        kind: SettingKind::Integer { min: 0, max: 10_000 },
+        default: "0",
+    },
+    Setting {
+        key: "reply_chance_percent",
+        description: "Chance of answering any message from a member with a generated one",
+        kind: SettingKind::Integer { min: 0, max: 100 },
+        default: "0",
+    },
+    Setting {
+        key: "conversation_max_turns",
+        description: "How many replies to its messages in a row the bot answers, 0 turns conversations off",
+        kind: SettingKind::Integer { min: 0, max: 100 },
+        default: "0",
+    },
+    Setting {
+        key: "conversation_timeout_seconds",
+        description: "A conversation with the bot ends once nobody replied to it for this long",
+        kind: SettingKind::Integer {
+            min: 30,
+            max: 86_400,
+        },
+        default: "300",
+    },
+    Setting {
+        key: "autopost_channels",
+        description: "Channels automatic messages may be posted into, separated by spaces, empty picks the most active channel",
+        kind: SettingKind::Channels { max_items: 25 },
+        default: "",
+    },
+    Setting {
+        key: "autopost_quiet_start_hour",
+        description: "Hour of the day, in the server's timezone, when automatic messages stop",
+        kind: SettingKind::Integer { min: 0, max: 23 },
+        default: "0",
+    },
+    Setting {
+        key: "autopost_quiet_end_hour",
+        description: "Hour of the day when automatic messages resume, the same as the start hour disables quiet hours",
+        kind: SettingKind::Integer { min: 0, max: 23 },
+        default: "0",
+    },
+    Setting {
+        key: "hall_of_fame_channel",
+        description: "Channel generated messages are reposted to once they are liked enough, empty turns the hall of fame off",
+        kind: SettingKind::Channels { max_items: 1 },
+        default: "",
+    },
+    Setting {
+        key: "hall_of_fame_threshold",
+        description: "Score a generated message needs to enter the hall of fame, members who liked it minus those who disliked it",
+        kind: SettingKind::Integer { min: 1, max: 1000 },
+        default: "5",
+    },
+    Setting {
+        key: "disabled_commands",
+        description: "Commands members can't use on this server, separated by commas, such as guess, leaderboard",
+        kind: SettingKind::Commands,
+        default: "",
+    },
+    Setting {
+        key: "timezone",
+        description: "The server's timezone, such as Europe/Berlin, used for quiet hours",
+        kind: SettingKind::Timezone,
+        default: "UTC",
+    },
+    Setting {
+        key: "store_bot_messages",
+        description: "Store and learn from messages sent by bots and webhooks",
+        kind: SettingKind::Boolean,
+        default: "false",
+    },
+];
+
+/// Looks up a setting by its key.
+pub fn find(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|setting| setting.key == key)
+}
+
+impl Setting {
+    /// Validates `value`, returning it in the form it is stored in.
+    pub fn parse(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+
+        match self.kind {
+            SettingKind::Integer { min, max } => match value.parse::<i64>() {
+                Ok(number) if (min..=max).contains(&number) => Ok(number.to_string()),
+                _ => Err(format!(
+                    "`{}` must be a whole number between {} and {}.",
+                    self.key, min, max
+                )),
+            },
+            SettingKind::Boolean => match value.to_lowercase().as_str() {
+                "true" | "on" | "yes" | "1" => Ok("true".into()),
+                "false" | "off" | "no" | "0" => Ok("false".into()),
+                _ => Err(format!("`{}` must be either true or false.", self.key)),
+            },
+            SettingKind::List { max_items } => {
+                let items: Vec<&str> = value.split_whitespace().collect();
+                if items.len() > max_items {
+                    return Err(format!(
+                        "`{}` can hold at most {} items.",
+                        self.key, max_items
+                    ));
+                }
+                Ok(items.join(" "))
+            }
+            SettingKind::Channels { max_items } => {
+                let ids: Option<Vec<u64>> = value.split_whitespace().map(parse_channel).collect();
+                match ids {
+                    Some(ids) if ids.len() <= max_items => {
+                        Ok(ids.iter().map(u64::to_string).collect::<Vec<_>>().join(" "))
+                    }
+                    Some(_) => Err(format!(
+                        "`{}` can hold at most {} channels.",
+                        self.key, max_items
+                    )),
+                    None => Err(format!(
+                        "`{}` must be channel mentions or ids, separated by spaces.",
+                        self.key
+                    )),
+                }
+            }
+            SettingKind::Commands => {
+                let mut names: Vec<&str> = Vec::new();
+
+                for item in value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                {
+                    let item = item.trim_start_matches('/');
+                    match crate::commands::COMMANDS
+                        .iter()
+                        .find(|command| command.name().eq_ignore_ascii_case(item))
+                    {
+                        Some(command)
+                            if crate::commands::PROTECTED_COMMANDS.contains(&command.name()) =>
+                        {
+                            return Err(format!(
+                                "`/{}` can't be listed in `{}`.",
+                                command.name(),
+                                self.key
+                            ));
+                        }
+                        Some(command) => {
+                            if !names.contains(&command.name()) {
+                                names.push(command.name());
+                            }
+                        }
+                        None => return Err(format!("`{}` isn't one of the bot's commands.", item)),
+                    }
+                }
+
+                Ok(names.join(", "))
+            }
+            SettingKind::Timezone => match value.parse::<Tz>() {
+                Ok(timezone) => Ok(timezone.name().to_string()),
+                Err(_) => Err(format!(
+                    "`{}` must be a timezone name, such as `Europe/Berlin` or `UTC`.",
+                    self.key
+                )),
+            },
+        }
+    }
+}
+
+/// Reads a channel mention such as `<#123>`, or a bare channel id.
+fn parse_channel(value: &str) -> Option<u64> {
+    value
+        .strip_prefix("<#")
+        .and_then(|value| value.strip_suffix('>'))
+        .unwrap_or(value)
+        .parse()
+        .ok()
+}
+
+/// A guild's settings, with defaults filled in for everything not configured.
+#[derive(Debug, Clone)]
+pub struct GuildSettings {
+    pub retention_max_age_days: u64,
+    pub retention_max_rows_per_channel: u64,
+    pub store_bot_messages: bool,
+    pub min_messages: usize,
+    pub fetch_limit: usize,
+    pub blacklist_prefixes: Vec<String>,
+    pub game_timeout_seconds: u64,
+    pub autopost_enabled: bool,
+    pub autopost_interval_seconds: u64,
+    pub autopost_jitter_seconds: u64,
+    pub autopost_after_messages: u64,
+    pub reply_chance_percent: u32,
+    pub conversation_max_turns: u64,
+    pub conversation_timeout_seconds: u64,
+    pub autopost_channels: Vec<u64>,
+    pub autopost_quiet_start_hour: u32,
+    pub autopost_quiet_end_hour: u32,
+    pub hall_of_fame_channel: Option<u64>,
+    pub hall_of_fame_threshold: i64,
+    pub disabled_commands: Vec<String>,
+    pub timezone: Tz,
+}
+
+impl GuildSettings {
+    pub fn from_values(values: &HashMap<String, String>) -> Self {
+        Self {
+            retention_max_age_days: value(values, "retention_max_age_days"),
+            retention_max_rows_per_channel: value(values, "retention_max_rows_per_channel"),
+            store_bot_messages: value(values, "store_bot_messages"),
+            min_messages: value(values, "min_messages"),
+            fetch_limit: value(values, "fetch_limit"),
+            blacklist_prefixes: list(values, "blacklist_prefixes"),
+            game_timeout_seconds: value(values, "game_timeout_seconds"),
+            autopost_enabled: value(values, "autopost_enabled"),
+            autopost_interval_seconds: value(values, "autopost_interval_seconds"),
+            autopost_jitter_seconds: value(values, "autopost_jitter_seconds"),
+            autopost_after_messages: value(values, "autopost_after_messages"),
+            reply_chance_percent: value(values, "reply_chance_percent"),
+            conversation_max_turns: value(values, "conversation_max_turns"),
+            conversation_timeout_seconds: value(values, "conversation_timeout_seconds"),
+            autopost_channels: list(values, "autopost_channels")
+                .iter()
+                .filter_map(|channel| channel.parse().ok())
+                .collect(),
+            autopost_quiet_start_hour: value(values, "autopost_quiet_start_hour"),
+            autopost_quiet_end_hour: value(values, "autopost_quiet_end_hour"),
+            hall_of_fame_channel: list(values, "hall_of_fame_channel")
+                .first()
+                .and_then(|channel| channel.parse().ok()),
+            hall_of_fame_threshold: value(values, "hall_of_fame_threshold"),
+            disabled_commands: names(values, "disabled_commands"),
+            timezone: value(values, "timezone"),
+        }
+    }
+
+    /// The blacklisted prefixes, in the form the storage queries take them.
+    pub fn prefixes(&self) -> Vec<&str> {
+        self.blacklist_prefixes.iter().map(String::as_str).collect()
+    }
+
+    /// Whether members may not use the command named `name` on this server.
+    pub fn is_command_disabled(&self, name: &str) -> bool {
+        self.disabled_commands
+            .iter()
+            .any(|disabled| disabled.eq_ignore_ascii_case(name))
+    }
+
+    /// The autopost wait range in seconds, the interval give or take the jitter.
+    pub fn autopost_wait_range(&self) -> (u64, u64) {
+        let interval = self.autopost_interval_seconds;
+        let jitter = self.autopost_jitter_seconds;
+        (interval.saturating_sub(jitter).max(60), interval + jitter)
+    }
+
+    /// Whether `now` falls within the quiet hours, in the guild's timezone.
+    pub fn is_quiet_time(&self, now: DateTime<Utc>) -> bool {
+        let start = self.autopost_quiet_start_hour;
+        let end = self.autopost_quiet_end_hour;
+        let hour = now.with_timezone(&self.timezone).hour();
+
+        // Quiet hours may wrap around midnight, such as 22 to 7
+        if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+
+    /// Loads a guild's settings, falling back to the defaults if they can't be read.
+    pub async fn load(database: &Database, guild_id: u64) -> Self {
+        match database.get_guild_settings(guild_id).await {
+            Ok(values) => Self::from_values(&values),
+            Err(e) => {
+                eprintln!("Failed to load guild settings: {}", e);
+                Self::from_values(&HashMap::new())
+            }
+        }
+    }
+}
+
+/// Parses the stored value of `key`, or its default if it isn't set or invalid.
+fn value<T: FromStr>(values: &HashMap<String, String>, key: &str) -> T {
+    let default = find(key)
+        .map(|setting| setting.default)
+        .expect("setting is registered");
+
+    values
+        .get(key)
+        .and_then(|value| value.parse().ok())
+        .or_else(|| default.parse().ok())
+        .expect("setting default is valid")
+}
+
+/// Splits the stored value of a list setting, or its default if it isn't set.
+fn list(values: &HashMap<String, String>, key: &str) -> Vec<String> {
+    let value = match values.get(key) {
+        Some(value) => value.as_str(),
+        None => find(key)
+            .map(|setting| setting.default)
+            .expect("setting is registered"),
+    };
+
+    value.split_whitespace().map(String::from).collect()
+}
+
+/// Splits the stored value of a comma separated setting, or its default if it isn't set.
+fn names(values: &HashMap<String, String>, key: &str) -> Vec<String> {
+    let value = match values.get(key) {
+        Some(value) => value.as_str(),
+        None => find(key)
+            .map(|setting| setting.default)
+            .expect("setting is registered"),
+    };
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,21 @@
+use serenity::all::GuildId;
+use std::sync::Arc;
+
+use crate::cooldowns::Cooldowns;
+use crate::database::Database;
+use crate::lookup_cache::LookupCache;
+use crate::utils::vocabulary::VocabularyCache;
+
+/// What every command is handed when it runs, shared for the whole run.
+pub struct AppState {
+    pub database: Arc<Database>,
+    /// The guilds commands are registered in instead of globally, see
+    /// `commands::command_guilds`.
+    pub command_guilds: Vec<GuildId>,
+    /// The recently built vocabulary of each channel, for `/generate` suggestions.
+    pub vocabularies: VocabularyCache,
+    /// Commands members and channels recently used, for the cooldowns of command rules.
+    pub cooldowns: Cooldowns,
+    /// Settings, channel flags and opt-outs looked up for incoming messages.
+    pub lookups: LookupCache,
+}
@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
+use serde::Serialize;
+
+use crate::database::{Database, MessageFilter, StoredMessage};
+use crate::utils::snowflake;
+
+/// Directory on the host that exports too large to attach are written to.
+pub const EXPORT_DIRECTORY: &str = "exports";
+
+/// Discord's attachment limit for bots, with some headroom.
+pub const MAX_ATTACHMENT_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "jsonl" | "json" => Some(Self::Jsonl),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jsonl => "jsonl",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// Where the finished export ended up.
+pub enum ExportOutput {
+    /// The export fit below the size limit and was kept in memory.
+    Memory(Vec<u8>),
+    /// The export was streamed to a file on the host.
+    File(PathBuf),
+}
+
+#[derive(Serialize)]
+struct ExportRow {
+    message_id: String,
+    author_id: String,
+    channel_id: String,
+    guild_id: String,
+    timestamp: String,
+    content: String,
+    is_bot: bool,
+}
+
+impl From<StoredMessage> for ExportRow {
+    fn from(message: StoredMessage) -> Self {
+        // Ids are written as strings, since JSON consumers tend to lose precision on u64s
+        Self {
+            message_id: message.message_id.to_string(),
+            author_id: message.author_id.to_string(),
+            channel_id: message.channel_id.to_string(),
+            guild_id: message.guild_id.to_string(),
+            timestamp: snowflake::to_datetime(message.message_id).to_rfc3339(),
+            content: message.content,
+            is_bot: message.is_bot,
+        }
+    }
+}
+
+/// A writer that buffers in memory until `limit` bytes, then moves everything
+/// over to a file at `path` and keeps writing there.
+struct SpillWriter {
+    buffer: Vec<u8>,
+    file: Option<io::BufWriter<std::fs::File>>,
+    limit: usize,
+    path: PathBuf,
+}
+
+impl SpillWriter {
+    fn new(limit: usize, path: PathBuf) -> Self {
+        Self {
+            buffer: Vec::new(),
+            file: None,
+            limit,
+            path,
+        }
+    }
+
+    fn finish(self) -> io::Result<ExportOutput> {
+        match self.file {
+            Some(mut file) => {
+                file.flush()?;
+                Ok(ExportOutput::File(self.path))
+            }
+            None => Ok(ExportOutput::Memory(self.buffer)),
+        }
+    }
+}
+
+impl Write for SpillWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() && self.buffer.len() + buf.len() > self.limit {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            let mut file = io::BufWriter::new(std::fs::File::create(&self.path)?);
+            file.write_all(&self.buffer)?;
+            self.buffer = Vec::new();
+            self.file = Some(file);
+        }
+
+        match &mut self.file {
+            Some(file) => file.write(buf),
+            None => self.buffer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Streams the messages matching `filter` into `writer`, returning how many
+/// rows were written.
+pub async fn write_messages<W: Write>(
+    database: &Database,
+    filter: &MessageFilter,
+    format: ExportFormat,
+    writer: W,
+) -> io::Result<(W, usize)> {
+    let mut stream = database.stream_messages(filter);
+    let mut rows = 0;
+
+    match format {
+        ExportFormat::Jsonl => {
+            let mut writer = writer;
+            while let Some(message) = stream.next().await {
+                let message = message.map_err(io::Error::other)?;
+                serde_json::to_writer(&mut writer, &ExportRow::from(message))?;
+                writer.write_all(b"\n")?;
+                rows += 1;
+            }
+            Ok((writer, rows))
+        }
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            while let Some(message) = stream.next().await {
+                let message = message.map_err(io::Error::other)?;
+                writer.serialize(ExportRow::from(message))?;
+                rows += 1;
+            }
+            let writer = writer.into_inner().map_err(|e| e.into_error())?;
+            Ok((writer, rows))
+        }
+    }
+}
+
+/// Exports the messages matching `filter`, keeping the result in memory when it
+/// is small enough to be attached to a Discord message, and spilling it to a
+/// file inside `directory` otherwise.
+pub async fn export_messages(
+    database: &Database,
+    filter: &MessageFilter,
+    format: ExportFormat,
+    directory: &Path,
+) -> io::Result<(ExportOutput, usize)> {
+    let path = directory.join(file_name(filter, format));
+    let writer = SpillWriter::new(MAX_ATTACHMENT_BYTES, path);
+
+    let (writer, rows) = write_messages(database, filter, format, writer).await?;
+    Ok((writer.finish()?, rows))
+}
+
+/// Builds a file name such as `guild-1234-20250101T120000.jsonl`.
+pub fn file_name(filter: &MessageFilter, format: ExportFormat) -> String {
+    format!(
+        "guild-{}-{}.{}",
+        filter.guild_id,
+        chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+        format.extension()
+    )
+}
@@ -1,29 +1,172 @@
 use rand::Rng;
 use std::sync::Arc;
 
-use serenity::all::{ChannelId, GuildId};
+use serenity::all::{ChannelId, GuildId, Message, UserId};
 
-use crate::database::Database;
-use crate::utils::markov_chain;
+use crate::database::{Database, Generation};
+use crate::settings::GuildSettings;
+use crate::utils::keywords;
+use crate::utils::markov_chain::Chain;
+use crate::utils::vocabulary::Vocabulary;
 
-const DATABASE_MESSAGE_FETCH_LIMIT: usize = 5000;
+/// How many of the latest generations members reacted to bias a chain.
+const FEEDBACK_LIMIT: u64 = 1000;
 
+/// The most a single generation's score can bias a chain, either way.
+const MAX_FEEDBACK_WEIGHT: i64 = 5;
+
+/// The fewest usable messages a member needs to have sent before they can be impersonated.
+const MIN_IMPERSONATION_MESSAGES: usize = 50;
+
+/// A generated message, along with how it was generated.
+#[derive(Debug, Clone)]
+pub struct GeneratedMessage {
+    pub content: String,
+    /// The word generation started from, if there was one.
+    pub seed: Option<String>,
+    /// The most words that could follow the seed.
+    pub max_words: usize,
+}
+
+/// Generates a message from a channel's history, or `None` if it has fewer
+/// than `settings.min_messages` usable messages.
 pub async fn generate_markov_message(
     guild_id: GuildId,
     channel_id: ChannelId,
     custom_word: Option<&str>,
     database: Arc<Database>,
-) -> Option<String> {
-    let blacklist_prefixes = [
-        "$", "&", "!", ".", "m.", ">", "<", "[", "]", "@", "#", "^", "*", ",", "https", "http",
-    ];
+    settings: &GuildSettings,
+) -> Option<GeneratedMessage> {
+    let markov_chain = train_markov_chain(guild_id, channel_id, database, settings).await?;
+
+    let max_words = rand::thread_rng().gen_range(1..15);
+    Some(GeneratedMessage {
+        content: markov_chain.generate(max_words, custom_word),
+        seed: custom_word.map(String::from),
+        max_words,
+    })
+}
+
+/// Generates a reply to `context`, the messages being answered with the most
+/// relevant first. The reply starts with the first of their keywords the
+/// channel's history can continue, or anywhere if none can.
+///
+/// Replies in `avoid` are never returned, `None` is returned instead if the
+/// channel has fewer than `settings.min_messages` usable messages or nothing
+/// new could be generated.
+pub async fn generate_markov_reply(
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    context: &[&str],
+    avoid: &[String],
+    database: Arc<Database>,
+    settings: &GuildSettings,
+) -> Option<GeneratedMessage> {
+    /// How many times each seed is tried before moving on to the next one.
+    const ATTEMPTS_PER_SEED: usize = 5;
+
+    let markov_chain = train_markov_chain(guild_id, channel_id, database, settings).await?;
+
+    // People type keywords in all cases, try them as written and in lowercase
+    let mut seeds: Vec<Option<String>> = keywords::extract(context, &settings.prefixes())
+        .into_iter()
+        .flat_map(|keyword| {
+            let lowercase = keyword.to_lowercase();
+            [keyword, lowercase]
+        })
+        .filter(|word| markov_chain.has_successors(word))
+        .map(Some)
+        .collect();
+    seeds.push(None);
+
+    let mut rng = rand::thread_rng();
+    for seed in &seeds {
+        for _ in 0..ATTEMPTS_PER_SEED {
+            let max_words = rng.gen_range(1..15);
+            let generated_sentence = markov_chain.generate(max_words, seed.as_deref());
+            if !avoid.contains(&generated_sentence) {
+                return Some(GeneratedMessage {
+                    content: generated_sentence,
+                    seed: seed.clone(),
+                    max_words,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+/// Generates a message in a member's style from what they sent in
+/// `channel_ids`, or `None` if they sent too few usable messages there.
+pub async fn generate_impersonation(
+    guild_id: GuildId,
+    author_id: UserId,
+    channel_ids: &[u64],
+    database: Arc<Database>,
+    settings: &GuildSettings,
+) -> Option<GeneratedMessage> {
+    let sentences = match database
+        .get_author_messages_for_markov(
+            guild_id.get(),
+            author_id.get(),
+            &settings.prefixes(),
+            settings.store_bot_messages,
+            Some(channel_ids),
+            settings.fetch_limit,
+        )
+        .await
+    {
+        Ok(sentences) => sentences,
+        Err(e) => {
+            eprintln!("Failed to fetch messages for impersonation: {}", e);
+            return None;
+        }
+    };
+
+    if sentences.len() < MIN_IMPERSONATION_MESSAGES {
+        return None;
+    }
+
+    let mut markov_chain = Chain::new();
+    markov_chain.train(sentences);
+
+    let max_words = rand::thread_rng().gen_range(1..15);
+    Some(GeneratedMessage {
+        content: markov_chain.generate(max_words, None),
+        seed: None,
+        max_words,
+    })
+}
+
+/// Builds the vocabulary of a channel's chain, or returns `None` if it has
+/// fewer than `settings.min_messages` usable messages.
+pub async fn build_vocabulary(
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    database: Arc<Database>,
+    settings: &GuildSettings,
+) -> Option<Vocabulary> {
+    let markov_chain = train_markov_chain(guild_id, channel_id, database, settings).await?;
+    Some(Vocabulary::new(&markov_chain))
+}
 
+/// Trains a chain on a channel's history, or returns `None` if it has fewer
+/// than `settings.min_messages` usable messages.
+async fn train_markov_chain(
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    database: Arc<Database>,
+    settings: &GuildSettings,
+) -> Option<Chain> {
     let sentences = match database
         .get_messages_for_markov(
             guild_id.get(),
             channel_id.get(),
-            &blacklist_prefixes,
-            DATABASE_MESSAGE_FETCH_LIMIT,
+            &settings.prefixes(),
+            settings.store_bot_messages,
+            // Sampling fewer than the threshold would never generate anything
+            settings.fetch_limit.max(settings.min_messages),
         )
         .await
     {
@@ -34,22 +177,76 @@ pub async fn generate_markov_message(
         }
     };
 
-    if sentences.len() < 500 {
+    if sentences.len() < settings.min_messages {
         return None;
     }
 
-    let mut rng = rand::thread_rng();
-
-    let mut markov_chain = markov_chain::Chain::new();
+    let mut markov_chain = Chain::new();
     markov_chain.train(sentences);
 
-    let max_words = rng.gen_range(1..15);
-    let generated_sentence = markov_chain.generate(max_words, custom_word);
-    Some(generated_sentence)
+    // Members' reactions to earlier generations make their transitions more or less likely
+    match database
+        .get_generation_feedback(guild_id.get(), FEEDBACK_LIMIT)
+        .await
+    {
+        Ok(feedback) => {
+            for (content, score) in feedback {
+                markov_chain.reinforce(
+                    &content,
+                    score.clamp(-MAX_FEEDBACK_WEIGHT, MAX_FEEDBACK_WEIGHT),
+                );
+            }
+        }
+        Err(e) => eprintln!("Failed to fetch generation feedback: {}", e),
+    }
+
+    Some(markov_chain)
+}
+
+/// Logs a posted generated message, so reactions to it are recorded.
+pub async fn log_generation(
+    database: &Database,
+    guild_id: GuildId,
+    posted: &Message,
+    source_channel_id: ChannelId,
+    generated: &GeneratedMessage,
+    settings: &GuildSettings,
+) {
+    let model_settings = serde_json::json!({
+        "fetch_limit": settings.fetch_limit,
+        "min_messages": settings.min_messages,
+        "store_bot_messages": settings.store_bot_messages,
+        "max_words": generated.max_words,
+    });
+
+    let generation = Generation {
+        message_id: posted.id.get(),
+        guild_id: guild_id.get(),
+        channel_id: posted.channel_id.get(),
+        source_channel_id: source_channel_id.get(),
+        content: generated.content.clone(),
+        seed: generated.seed.clone(),
+        settings: model_settings.to_string(),
+        created_at: posted.timestamp.unix_timestamp(),
+        score: 0,
+        featured_at: None,
+    };
+
+    if let Err(e) = database.log_generation(&generation).await {
+        eprintln!("Failed to log generation: {}", e);
+    }
 }
 
-pub async fn get_most_popular_channel(guild_id: GuildId, database: Arc<Database>) -> u64 {
-    match database.get_most_popular_channel(guild_id.get()).await {
+/// Returns the most popular channel among `channel_ids`, or 0 if there is none.
+pub async fn get_most_popular_channel(
+    guild_id: GuildId,
+    channel_ids: &[u64],
+    database: Arc<Database>,
+) -> u64 {
+    match database
+        .get_most_popular_channel(guild_id.get(), Some(channel_ids))
+        .await
+    {
         Ok(channel_id) => channel_id,
         Err(e) => {
             eprintln!("Failed to get most popular channel: {}", e);
@@ -0,0 +1,48 @@
+/// Common words that say nothing about what a message is about.
+const STOPWORDS: &[&str] = &[
+    "about", "after", "again", "all", "also", "and", "any", "are", "because", "been", "before",
+    "but", "can", "could", "did", "does", "doing", "dont", "for", "from", "get", "got", "had",
+    "has", "have", "her", "here", "him", "his", "how", "its", "just", "like", "more", "not", "now",
+    "one", "only", "our", "out", "really", "said", "say", "she", "should", "some", "than", "that",
+    "the", "their", "them", "then", "there", "these", "they", "this", "too", "very", "was", "way",
+    "were", "what", "when", "where", "which", "who", "why", "will", "with", "would", "yeah", "yes",
+    "you", "your",
+];
+
+/// Picks the words most likely to say what the texts are about, in order of
+/// preference: earlier texts first, and longer words first within a text.
+///
+/// Mentions, emojis, links, and words starting with a blacklisted prefix are
+/// skipped, as are short and common words.
+pub fn extract(texts: &[&str], blacklist_prefixes: &[&str]) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+
+    for text in texts {
+        let mut words: Vec<&str> = text
+            .split_whitespace()
+            .filter(|word| !word.starts_with('<') && !word.contains("://"))
+            .filter(|word| {
+                !blacklist_prefixes
+                    .iter()
+                    .any(|prefix| word.starts_with(prefix))
+            })
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+            .filter(|word| word.chars().count() >= 3)
+            .filter(|word| !STOPWORDS.contains(&word.to_lowercase().as_str()))
+            .collect();
+
+        // Stable, so equally long words keep the order they were written in
+        words.sort_by_key(|word| std::cmp::Reverse(word.chars().count()));
+
+        for word in words {
+            if !keywords
+                .iter()
+                .any(|keyword| keyword.eq_ignore_ascii_case(word))
+            {
+                keywords.push(word.to_string());
+            }
+        }
+    }
+
+    keywords
+}
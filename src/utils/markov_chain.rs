@@ -35,6 +35,54 @@ impl Chain {
         }
     }
 
+    /// Biases the transitions of `sentence` the chain already knows, making them
+    /// more likely for a positive `weight` and less likely for a negative one.
+    /// Transitions are never added, nor removed entirely.
+    pub fn reinforce(&mut self, sentence: &str, weight: i64) {
+        let words: Vec<&str> = sentence.split_whitespace().collect();
+        for window in words.windows(2) {
+            if let [first, second] = window {
+                let next_words = match self.chains.get_mut(*first) {
+                    Some(next_words) => next_words,
+                    None => continue,
+                };
+
+                let count = next_words.iter().filter(|word| word == second).count();
+                if count == 0 {
+                    continue;
+                }
+
+                if weight > 0 {
+                    for _ in 0..weight {
+                        next_words.push(second.to_string());
+                    }
+                } else {
+                    // Keep one copy, so the transition stays possible
+                    let mut remove = (weight.unsigned_abs() as usize).min(count - 1);
+                    next_words.retain(|word| {
+                        if remove > 0 && word == second {
+                            remove -= 1;
+                            return false;
+                        }
+                        true
+                    });
+                }
+            }
+        }
+    }
+
+    /// Every word the chain knows a follower of, with each follower as often as it was seen.
+    pub fn states(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.chains
+            .iter()
+            .map(|(word, next_words)| (word.as_str(), next_words.as_slice()))
+    }
+
+    /// Whether the chain knows a word that can follow `word`.
+    pub fn has_successors(&self, word: &str) -> bool {
+        self.chains.get(word).is_some_and(|words| !words.is_empty())
+    }
+
     pub fn generate(&self, word_limit: usize, custom_word: Option<&str>) -> String {
         // Initiate the random number generator
         let mut rng = rand::thread_rng();
@@ -1,3 +1,10 @@
+pub mod export;
 pub mod helpers;
+pub mod keywords;
 pub mod markov_chain;
+pub mod reactions;
+pub mod sampling;
+pub mod snowflake;
 pub mod string_cmp;
+pub mod visibility;
+pub mod vocabulary;
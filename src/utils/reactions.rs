@@ -0,0 +1,16 @@
+use serenity::all::ReactionType;
+
+/// Reactions that count as liking a generated message.
+const POSITIVE: &[&str] = &["👍", "❤️", "❤", "😂", "🤣", "⭐"];
+
+/// Reactions that count as disliking a generated message.
+const NEGATIVE: &[&str] = &["👎"];
+
+/// How much a reaction adds to a generated message's score, 0 if it doesn't count.
+pub fn value(emoji: &ReactionType) -> i64 {
+    match emoji {
+        ReactionType::Unicode(emoji) if POSITIVE.contains(&emoji.as_str()) => 1,
+        ReactionType::Unicode(emoji) if NEGATIVE.contains(&emoji.as_str()) => -1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+use std::future::Future;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// How many rounds of random lookups a sample may take before giving up with
+/// whatever it found.
+const MAX_SAMPLING_ROUNDS: usize = 5;
+
+/// Maximum amount of positions looked up in a single `IN (...)` query.
+const SAMPLING_CHUNK_SIZE: usize = 500;
+
+/// Draws up to `amount` distinct positions from `lo..=hi` that are not in
+/// `tried`, and marks them as tried.
+///
+/// Every untried position is equally likely to be picked, so rejecting the
+/// positions that turn out to be deleted or filtered out still leaves a
+/// uniform sample of the ones that remain.
+pub fn draw_positions<R: Rng>(
+    rng: &mut R,
+    lo: i64,
+    hi: i64,
+    amount: usize,
+    tried: &mut HashSet<i64>,
+) -> Vec<i64> {
+    if hi < lo || amount == 0 {
+        return Vec::new();
+    }
+
+    let span = (hi - lo + 1) as u64;
+    let remaining = span.saturating_sub(tried.len() as u64);
+
+    // Few positions left, hand out all of them
+    if remaining <= amount as u64 {
+        let positions: Vec<i64> = (lo..=hi).filter(|p| !tried.contains(p)).collect();
+        tried.extend(&positions);
+        return positions;
+    }
+
+    let mut positions = Vec::with_capacity(amount);
+    while positions.len() < amount {
+        let position = rng.gen_range(lo..=hi);
+        if tried.insert(position) {
+            positions.push(position);
+        }
+    }
+
+    positions
+}
+
+/// Samples up to `limit` rows uniformly from the ordinals `lo..=hi`.
+///
+/// `fetch` is given a chunk of positions and returns the rows at those
+/// positions that pass its filters. Every round over-draws to make up for the
+/// positions that were rejected, drawing at least `min_batch` positions.
+pub async fn sample<T, F, Fut>(
+    lo: i64,
+    hi: i64,
+    limit: usize,
+    min_batch: usize,
+    mut fetch: F,
+) -> Result<Vec<T>, sqlx::Error>
+where
+    F: FnMut(Vec<i64>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>>,
+{
+    // `ThreadRng` isn't `Send`, so it can't be held across the queries
+    let mut rng = StdRng::from_entropy();
+    let mut tried = HashSet::new();
+    let mut sampled = Vec::with_capacity(limit);
+
+    for _ in 0..MAX_SAMPLING_ROUNDS {
+        if sampled.len() >= limit {
+            break;
+        }
+
+        let needed = limit - sampled.len();
+        let amount = (needed * 2).max(min_batch);
+        let positions = draw_positions(&mut rng, lo, hi, amount, &mut tried);
+        if positions.is_empty() {
+            break;
+        }
+
+        let mut found = Vec::new();
+        for chunk in positions.chunks(SAMPLING_CHUNK_SIZE) {
+            found.extend(fetch(chunk.to_vec()).await?);
+        }
+
+        // Rows come back in index order, shuffle them before cutting off the excess
+        found.shuffle(&mut rng);
+        found.truncate(needed);
+        sampled.extend(found);
+    }
+
+    sampled.shuffle(&mut rng);
+    Ok(sampled)
+}
+
+/// Samples up to `limit` rows from the already known `positions`, such as the
+/// ordinals of one member's messages, which are too scattered over the whole
+/// range for `sample` to find.
+///
+/// `fetch` is given a chunk of the picked positions and returns their rows.
+pub async fn sample_known<T, F, Fut>(
+    mut positions: Vec<i64>,
+    limit: usize,
+    mut fetch: F,
+) -> Result<Vec<T>, sqlx::Error>
+where
+    F: FnMut(Vec<i64>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>>,
+{
+    let mut rng = StdRng::from_entropy();
+    positions.shuffle(&mut rng);
+    positions.truncate(limit);
+
+    let mut sampled = Vec::with_capacity(positions.len());
+    for chunk in positions.chunks(SAMPLING_CHUNK_SIZE) {
+        sampled.extend(fetch(chunk.to_vec()).await?);
+    }
+
+    sampled.shuffle(&mut rng);
+    Ok(sampled)
+}
+
+/// Picks a single row uniformly from the ordinals `lo..=hi`.
+///
+/// Random lookups keep missing when few of the rows pass the filters, so once
+/// `sample` comes back empty `fallback` is asked for a row instead. It should
+/// sample a narrower range, such as only the allowed channels, since counting
+/// through the matching rows doesn't stay fast on large guilds.
+pub async fn pick<T, F, Fut, G, GFut>(
+    lo: i64,
+    hi: i64,
+    min_batch: usize,
+    fetch: F,
+    fallback: G,
+) -> Result<Option<T>, sqlx::Error>
+where
+    F: FnMut(Vec<i64>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>>,
+    G: FnOnce() -> GFut,
+    GFut: Future<Output = Result<Option<T>, sqlx::Error>>,
+{
+    match sample(lo, hi, 1, min_batch, fetch)
+        .await?
+        .into_iter()
+        .next()
+    {
+        Some(row) => Ok(Some(row)),
+        None => fallback().await,
+    }
+}
+
+/// The amount of positions in `ranges`, which are `(key, lo, hi)` ranges of
+/// ordinals such as each channel's `channel_seq`.
+pub fn span(ranges: &[(u64, i64, i64)]) -> i64 {
+    ranges.iter().map(|&(_, lo, hi)| (hi - lo + 1).max(0)).sum()
+}
+
+/// Maps a position in `0..span(ranges)` to the key and ordinal it stands for,
+/// so `sample` can draw from several ranges at once.
+pub fn locate(ranges: &[(u64, i64, i64)], mut position: i64) -> Option<(u64, i64)> {
+    for &(key, lo, hi) in ranges {
+        let len = (hi - lo + 1).max(0);
+        if position < len {
+            return Some((key, lo + position));
+        }
+        position -= len;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looks up `positions` in a fake table holding only the `present` ordinals.
+    fn fetch_from(
+        present: &[i64],
+    ) -> impl FnMut(Vec<i64>) -> std::future::Ready<Result<Vec<i64>, sqlx::Error>> + '_ {
+        move |positions| {
+            std::future::ready(Ok(positions
+                .into_iter()
+                .filter(|position| present.contains(position))
+                .collect()))
+        }
+    }
+
+    /// Picks any row of the fake table, standing in for the narrower database fallbacks.
+    fn fallback_from(
+        present: &[i64],
+    ) -> impl FnOnce() -> std::future::Ready<Result<Option<i64>, sqlx::Error>> + '_ {
+        move || std::future::ready(Ok(present.choose(&mut rand::thread_rng()).copied()))
+    }
+
+    #[test]
+    fn draws_distinct_positions_in_range() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut tried = HashSet::new();
+
+        let first = draw_positions(&mut rng, 10, 1_000, 50, &mut tried);
+        let second = draw_positions(&mut rng, 10, 1_000, 50, &mut tried);
+
+        assert_eq!(first.len(), 50);
+        assert_eq!(second.len(), 50);
+        assert!(first
+            .iter()
+            .chain(&second)
+            .all(|position| (10..=1_000).contains(position)));
+        assert!(first.iter().all(|position| !second.contains(position)));
+        assert_eq!(tried.len(), 100);
+    }
+
+    #[test]
+    fn hands_out_the_rest_of_a_small_range() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut tried = HashSet::from([2, 4]);
+
+        let mut positions = draw_positions(&mut rng, 1, 5, 20, &mut tried);
+        positions.sort_unstable();
+
+        assert_eq!(positions, vec![1, 3, 5]);
+        assert!(draw_positions(&mut rng, 1, 5, 20, &mut tried).is_empty());
+    }
+
+    #[test]
+    fn draws_nothing_from_an_empty_range() {
+        let mut rng = StdRng::seed_from_u64(3);
+
+        assert!(draw_positions(&mut rng, 5, 4, 20, &mut HashSet::new()).is_empty());
+        assert!(draw_positions(&mut rng, 1, 5, 0, &mut HashSet::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn samples_a_single_row_range() {
+        let sampled = sample(7, 7, 5, 20, fetch_from(&[7])).await.unwrap();
+
+        assert_eq!(sampled, vec![7]);
+    }
+
+    #[tokio::test]
+    async fn samples_up_to_the_limit() {
+        let present: Vec<i64> = (0..1_000).step_by(2).collect();
+        let sampled = sample(0, 999, 100, 20, fetch_from(&present)).await.unwrap();
+
+        assert_eq!(sampled.len(), 100);
+        assert!(sampled.iter().all(|row| row % 2 == 0));
+        assert_eq!(sampled.iter().collect::<HashSet<_>>().len(), 100);
+    }
+
+    #[tokio::test]
+    async fn samples_known_positions_up_to_the_limit() {
+        let present: Vec<i64> = (0..2_000).map(|row| row * 37).collect();
+
+        let sampled = sample_known(present.clone(), 600, fetch_from(&present))
+            .await
+            .unwrap();
+        assert_eq!(sampled.len(), 600);
+        assert_eq!(sampled.iter().collect::<HashSet<_>>().len(), 600);
+
+        let sampled = sample_known(vec![37, 74], 600, fetch_from(&present))
+            .await
+            .unwrap();
+        assert_eq!(sampled.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn picks_the_only_row_of_a_sparse_range() {
+        let present = [123_456];
+
+        for _ in 0..10 {
+            let picked = pick(
+                0,
+                1_000_000,
+                20,
+                fetch_from(&present),
+                fallback_from(&present),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(picked, Some(123_456));
+        }
+    }
+
+    #[tokio::test]
+    async fn picks_nothing_when_no_rows_match() {
+        let picked = pick(0, 1_000, 20, fetch_from(&[]), fallback_from(&[]))
+            .await
+            .unwrap();
+
+        assert_eq!(picked, None);
+    }
+
+    #[test]
+    fn locates_positions_across_ranges() {
+        let ranges = [(1, 10, 12), (2, 5, 4), (3, 0, 1)];
+
+        assert_eq!(span(&ranges), 5);
+        let located: Vec<_> = (0..6).map(|position| locate(&ranges, position)).collect();
+        assert_eq!(
+            located,
+            vec![
+                Some((1, 10)),
+                Some((1, 11)),
+                Some((1, 12)),
+                Some((3, 0)),
+                Some((3, 1)),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// First second of 2015, the epoch Discord snowflakes count from.
+const DISCORD_EPOCH: i64 = 1_420_070_400_000;
+
+/// Returns the smallest snowflake that could have been created at `time`.
+pub fn from_datetime(time: DateTime<Utc>) -> u64 {
+    let millis = (time.timestamp_millis() - DISCORD_EPOCH).max(0) as u64;
+    millis << 22
+}
+
+/// Returns the time a snowflake was created at.
+pub fn to_datetime(snowflake: u64) -> DateTime<Utc> {
+    let millis = (snowflake >> 22) as i64 + DISCORD_EPOCH;
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+/// Parses a `YYYY-MM-DD` date into the snowflake of its first millisecond (UTC).
+pub fn from_date(date: &str) -> Option<u64> {
+    let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?;
+    Some(from_datetime(date.and_hms_opt(0, 0, 0)?.and_utc()))
+}
+
+/// Like `from_date`, but returns the snowflake right after the given day ends,
+/// so it can be used as an exclusive upper bound.
+pub fn after_date(date: &str) -> Option<u64> {
+    let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?;
+    Some(from_datetime(
+        date.succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc(),
+    ))
+}
@@ -0,0 +1,92 @@
+use serenity::all::{
+    Cache, ChannelId, ChannelType, Guild, GuildChannel, GuildId, Member, PermissionOverwriteType,
+    Permissions, RoleId,
+};
+
+/// Permissions needed to read a channel's existing messages.
+const READ_PERMISSIONS: Permissions =
+    Permissions::VIEW_CHANNEL.union(Permissions::READ_MESSAGE_HISTORY);
+
+/// Returns the ids of the channels in a guild that `member` can read.
+pub fn readable_by_member(cache: &Cache, guild_id: GuildId, member: &Member) -> Vec<u64> {
+    readable_channels(cache, guild_id, |guild, channel| {
+        guild.user_permissions_in(channel, member)
+    })
+}
+
+/// Returns the ids of the channels in a guild that @everyone can read.
+pub fn readable_by_everyone(cache: &Cache, guild_id: GuildId) -> Vec<u64> {
+    readable_channels(cache, guild_id, everyone_permissions_in)
+}
+
+/// Returns the channel a cached thread was started in, or `None` for anything
+/// that isn't a thread.
+pub fn thread_parent(cache: &Cache, guild_id: GuildId, channel_id: ChannelId) -> Option<u64> {
+    let guild = cache.guild(guild_id)?;
+
+    guild
+        .threads
+        .iter()
+        .find(|thread| thread.id == channel_id)
+        .and_then(|thread| thread.parent_id)
+        .map(|parent_id| parent_id.get())
+}
+
+/// Resolves readable channels from the cache. Nothing is readable if the guild isn't cached.
+fn readable_channels(
+    cache: &Cache,
+    guild_id: GuildId,
+    permissions_in: impl Fn(&Guild, &GuildChannel) -> Permissions,
+) -> Vec<u64> {
+    let guild = match cache.guild(guild_id) {
+        Some(guild) => guild,
+        None => return Vec::new(),
+    };
+
+    let mut readable: Vec<u64> = guild
+        .channels
+        .values()
+        .filter(|channel| permissions_in(&guild, channel).contains(READ_PERMISSIONS))
+        .map(|channel| channel.id.get())
+        .collect();
+
+    // Public threads follow their parent, private ones are left out since
+    // membership isn't cached
+    let threads: Vec<u64> = guild
+        .threads
+        .iter()
+        .filter(|thread| thread.kind != ChannelType::PrivateThread)
+        .filter(|thread| {
+            thread
+                .parent_id
+                .is_some_and(|parent_id| readable.contains(&parent_id.get()))
+        })
+        .map(|thread| thread.id.get())
+        .collect();
+
+    readable.extend(threads);
+    readable
+}
+
+/// Calculates what @everyone may do in a channel, from the role and its overwrite.
+fn everyone_permissions_in(guild: &Guild, channel: &GuildChannel) -> Permissions {
+    let everyone = RoleId::new(guild.id.get());
+
+    let mut permissions = guild
+        .roles
+        .get(&everyone)
+        .map(|role| role.permissions)
+        .unwrap_or_else(Permissions::empty);
+
+    if permissions.contains(Permissions::ADMINISTRATOR) {
+        return Permissions::all();
+    }
+
+    for overwrite in &channel.permission_overwrites {
+        if matches!(overwrite.kind, PermissionOverwriteType::Role(role_id) if role_id == everyone) {
+            permissions = (permissions & !overwrite.deny) | overwrite.allow;
+        }
+    }
+
+    permissions
+}
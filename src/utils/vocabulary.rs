@@ -0,0 +1,125 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use serenity::all::ChannelId;
+use tokio::time::{Duration, Instant};
+
+use crate::utils::markov_chain::Chain;
+
+/// How long a channel's vocabulary is reused before it is built again.
+const CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// The longest suggestion, as seed words are limited to 50 characters.
+const MAX_SUGGESTION_LENGTH: usize = 50;
+
+/// The words and word pairs generation can start from in a channel, most
+/// frequent first.
+#[derive(Debug, Default)]
+pub struct Vocabulary {
+    entries: Vec<(String, usize)>,
+}
+
+impl Vocabulary {
+    pub fn new(chain: &Chain) -> Self {
+        let mut entries = Vec::new();
+
+        for (word, next_words) in chain.states() {
+            entries.push((word.to_string(), next_words.len()));
+
+            // A pair is only worth suggesting if generation can go on after it
+            let mut pairs: HashMap<&str, usize> = HashMap::new();
+            for next_word in next_words {
+                if chain.has_successors(next_word) {
+                    *pairs.entry(next_word).or_default() += 1;
+                }
+            }
+            entries.extend(
+                pairs
+                    .into_iter()
+                    .map(|(next_word, count)| (format!("{} {}", word, next_word), count)),
+            );
+        }
+
+        entries.retain(|(entry, _)| entry.chars().count() <= MAX_SUGGESTION_LENGTH);
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Self { entries }
+    }
+
+    /// Suggests up to `limit` entries for what a member typed so far. Entries
+    /// starting with it come before those only containing it, the most
+    /// frequent first. Word pairs are only suggested once a space was typed.
+    pub fn suggest(&self, typed: &str, limit: usize) -> Vec<&str> {
+        let typed = typed
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        let pairs = typed.contains(' ');
+
+        let mut starting = Vec::new();
+        let mut containing = Vec::new();
+
+        for (entry, _) in &self.entries {
+            if entry.contains(' ') != pairs {
+                continue;
+            }
+
+            let lowercase = entry.to_lowercase();
+            if lowercase.starts_with(&typed) {
+                starting.push(entry.as_str());
+                if starting.len() >= limit {
+                    break;
+                }
+            } else if containing.len() < limit && lowercase.contains(&typed) {
+                containing.push(entry.as_str());
+            }
+        }
+
+        starting.extend(containing);
+        starting.truncate(limit);
+        starting
+    }
+}
+
+/// A vocabulary and when it was built.
+type Built = (Instant, Arc<Vocabulary>);
+
+/// The recently built vocabulary of each channel, so suggestions keep up with typing.
+/// Clones share the same cache, so vocabularies can be built in the background.
+#[derive(Clone, Default)]
+pub struct VocabularyCache {
+    channels: Arc<Mutex<HashMap<ChannelId, Built>>>,
+    /// Channels whose vocabulary is being built, so typing doesn't start another build.
+    building: Arc<Mutex<HashSet<ChannelId>>>,
+}
+
+impl VocabularyCache {
+    /// Returns a channel's vocabulary, if it was built recently enough.
+    pub fn get(&self, channel_id: ChannelId) -> Option<Arc<Vocabulary>> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(&channel_id)
+            .filter(|(built_at, _)| built_at.elapsed() < CACHE_TTL)
+            .map(|(_, vocabulary)| vocabulary.clone())
+    }
+
+    /// Marks a channel's vocabulary as being built, returning `false` if it
+    /// already was.
+    pub fn start_building(&self, channel_id: ChannelId) -> bool {
+        self.building.lock().unwrap().insert(channel_id)
+    }
+
+    /// Caches a channel's freshly built vocabulary, forgetting stale ones.
+    pub fn insert(&self, channel_id: ChannelId, vocabulary: Vocabulary) -> Arc<Vocabulary> {
+        let vocabulary = Arc::new(vocabulary);
+        self.building.lock().unwrap().remove(&channel_id);
+
+        let mut channels = self.channels.lock().unwrap();
+        channels.retain(|_, (built_at, _)| built_at.elapsed() < CACHE_TTL);
+        channels.insert(channel_id, (Instant::now(), vocabulary.clone()));
+
+        vocabulary
+    }
+}